@@ -0,0 +1,238 @@
+//! Conversions of values into elements of the sponge field
+
+use crate::{Permutation, Poseidon};
+use halo2curves::bn256;
+use halo2curves::group::ff::PrimeField;
+use halo2curves::pasta;
+
+/// `AbsorbableInto` converts a value into elements of the sponge field `F`.
+/// Number of elements depends only on the type of the value so that encodings
+/// of consecutive values can't be confused
+pub trait AbsorbableInto<F: PrimeField> {
+    /// Returns elements to be absorbed
+    fn absorbable(&self) -> Vec<F>;
+}
+
+impl<F: PrimeField> AbsorbableInto<F> for F {
+    fn absorbable(&self) -> Vec<F> {
+        vec![*self]
+    }
+}
+
+/// `Absorb` feeds a structured value into a hasher so that heterogeneous
+/// values such as `(scalar, n)` can be absorbed together. Encoding depends
+/// only on the type: fixed size values absorb a fixed number of elements
+/// while slices and vectors are prefixed with their length
+pub trait Absorb<F: PrimeField> {
+    /// Appends the value to the absorption line of the hasher
+    fn absorb_into<const T: usize, const RATE: usize, P: Permutation<F, T> + Clone>(
+        &self,
+        hasher: &mut Poseidon<F, T, RATE, P>,
+    );
+}
+
+impl<F: PrimeField, A: Absorb<F> + ?Sized> Absorb<F> for &A {
+    fn absorb_into<const T: usize, const RATE: usize, P: Permutation<F, T> + Clone>(
+        &self,
+        hasher: &mut Poseidon<F, T, RATE, P>,
+    ) {
+        (*self).absorb_into(hasher)
+    }
+}
+
+impl<F: PrimeField> Absorb<F> for u64 {
+    fn absorb_into<const T: usize, const RATE: usize, P: Permutation<F, T> + Clone>(
+        &self,
+        hasher: &mut Poseidon<F, T, RATE, P>,
+    ) {
+        hasher.update_u64(*self)
+    }
+}
+
+impl<F: PrimeField> Absorb<F> for usize {
+    fn absorb_into<const T: usize, const RATE: usize, P: Permutation<F, T> + Clone>(
+        &self,
+        hasher: &mut Poseidon<F, T, RATE, P>,
+    ) {
+        hasher.update_usize(*self)
+    }
+}
+
+impl<F: PrimeField> Absorb<F> for bool {
+    fn absorb_into<const T: usize, const RATE: usize, P: Permutation<F, T> + Clone>(
+        &self,
+        hasher: &mut Poseidon<F, T, RATE, P>,
+    ) {
+        hasher.update_u64(*self as u64)
+    }
+}
+
+impl<F: PrimeField, A: Absorb<F>> Absorb<F> for [A] {
+    fn absorb_into<const T: usize, const RATE: usize, P: Permutation<F, T> + Clone>(
+        &self,
+        hasher: &mut Poseidon<F, T, RATE, P>,
+    ) {
+        hasher.update_usize(self.len());
+        for value in self.iter() {
+            value.absorb_into(hasher);
+        }
+    }
+}
+
+impl<F: PrimeField, A: Absorb<F>> Absorb<F> for Vec<A> {
+    fn absorb_into<const T: usize, const RATE: usize, P: Permutation<F, T> + Clone>(
+        &self,
+        hasher: &mut Poseidon<F, T, RATE, P>,
+    ) {
+        self.as_slice().absorb_into(hasher)
+    }
+}
+
+impl<F: PrimeField, A: Absorb<F>, const N: usize> Absorb<F> for [A; N] {
+    fn absorb_into<const T: usize, const RATE: usize, P: Permutation<F, T> + Clone>(
+        &self,
+        hasher: &mut Poseidon<F, T, RATE, P>,
+    ) {
+        for value in self.iter() {
+            value.absorb_into(hasher);
+        }
+    }
+}
+
+macro_rules! impl_absorb_tuple {
+    ($(($($A:ident $i:tt),+)),*) => {
+        $(
+            impl<F: PrimeField, $($A: Absorb<F>),+> Absorb<F> for ($($A,)+) {
+                fn absorb_into<const T: usize, const RATE: usize, P: Permutation<F, T> + Clone>(
+                    &self,
+                    hasher: &mut Poseidon<F, T, RATE, P>,
+                ) {
+                    $(self.$i.absorb_into(hasher);)+
+                }
+            }
+        )*
+    };
+}
+
+impl_absorb_tuple!(
+    (A 0),
+    (A 0, B 1),
+    (A 0, B 1, C 2),
+    (A 0, B 1, C 2, D 3)
+);
+
+/// Number of whole bytes that fit in an element of `F` without reduction
+pub fn bytes_per_element<F: PrimeField>() -> usize {
+    F::CAPACITY as usize / 8
+}
+
+/// Splits little-endian representation of a foreign field element into limbs
+/// of [`bytes_per_element`] bytes so that each limb fits in `F` without reduction
+pub(crate) fn decompose<G: PrimeField, F: PrimeField>(e: &G) -> Vec<F> {
+    pack_bytes(e.to_repr().as_ref())
+}
+
+/// Packs bytes into elements of [`bytes_per_element`] bytes each. Bytes of an
+/// element are read as a little-endian integer
+pub(crate) fn pack_bytes<F: PrimeField>(bytes: &[u8]) -> Vec<F> {
+    let radix = F::from(256);
+    bytes
+        .chunks(bytes_per_element::<F>())
+        .map(|limb| {
+            limb.iter()
+                .rev()
+                .fold(F::ZERO, |acc, byte| acc * radix + F::from(*byte as u64))
+        })
+        .collect()
+}
+
+macro_rules! impl_absorbable_into {
+    ($($G:ty => $F:ty),*) => {
+        $(
+            impl AbsorbableInto<$F> for $G {
+                fn absorbable(&self) -> Vec<$F> {
+                    decompose(self)
+                }
+            }
+        )*
+    };
+}
+
+impl_absorbable_into!(
+    bn256::Fq => bn256::Fr,
+    bn256::Fr => bn256::Fq,
+    pasta::Fp => pasta::Fq,
+    pasta::Fq => pasta::Fp
+);
+
+macro_rules! impl_absorb_field {
+    ($($G:ty => $F:ty),*) => {
+        $(
+            impl Absorb<$F> for $G {
+                fn absorb_into<const T: usize, const RATE: usize, P: Permutation<$F, T> + Clone>(
+                    &self,
+                    hasher: &mut Poseidon<$F, T, RATE, P>,
+                ) {
+                    hasher.update_foreign(&[*self])
+                }
+            }
+        )*
+    };
+}
+
+impl_absorb_field!(
+    bn256::Fr => bn256::Fr,
+    bn256::Fq => bn256::Fq,
+    bn256::Fq => bn256::Fr,
+    bn256::Fr => bn256::Fq,
+    pasta::Fp => pasta::Fp,
+    pasta::Fq => pasta::Fq,
+    pasta::Fp => pasta::Fq,
+    pasta::Fq => pasta::Fp
+);
+
+#[cfg(test)]
+mod tests {
+    use super::{bytes_per_element, pack_bytes, AbsorbableInto};
+    use crate::Poseidon;
+    use halo2curves::group::ff::Field;
+    use halo2curves::{bn256, pasta};
+    use rand_core::OsRng;
+
+    #[test]
+    fn bytes_per_element_of_fields() {
+        assert_eq!(bytes_per_element::<bn256::Fr>(), 31);
+        assert_eq!(bytes_per_element::<pasta::Fp>(), 31);
+
+        let bytes = (0..=62).collect::<Vec<u8>>();
+        let packed = pack_bytes::<bn256::Fr>(&bytes);
+        assert_eq!(packed.len(), 3);
+        assert_eq!(packed[2], bn256::Fr::from(62));
+    }
+
+    #[test]
+    fn absorb_tuple() {
+        let scalar = bn256::Fr::random(OsRng);
+        let base = bn256::Fq::random(OsRng);
+        let values = vec![bn256::Fr::random(OsRng), bn256::Fr::random(OsRng)];
+
+        let mut poseidon_0 = Poseidon::<bn256::Fr, 3, 2>::new(8, 57);
+        poseidon_0.absorb(&(scalar, base, 7u64, &values));
+
+        let mut poseidon_1 = Poseidon::<bn256::Fr, 3, 2>::new(8, 57);
+        poseidon_1.update(&[scalar]);
+        poseidon_1.update(&base.absorbable());
+        poseidon_1.update_u64(7);
+        poseidon_1.update_usize(values.len());
+        poseidon_1.update(&values);
+        assert_eq!(poseidon_0.squeeze(), poseidon_1.squeeze());
+
+        // Length prefix separates slices that concatenate to the same elements
+        let hash = |value: (&[bn256::Fr], &[bn256::Fr])| {
+            let mut poseidon = Poseidon::<bn256::Fr, 3, 2>::new(8, 57);
+            poseidon.absorb(&value);
+            poseidon.squeeze()
+        };
+        assert_ne!(hash((&values[..1], &values[1..])), hash((&values, &[])));
+    }
+}
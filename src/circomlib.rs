@@ -0,0 +1,87 @@
+//! Specs compatible with circomlib `poseidon` templates over BN256 scalar
+//! field. See https://github.com/iden3/circomlib/blob/master/circuits/poseidon.circom
+
+use crate::{Bn256Std, PoseidonParams, Spec, State};
+use halo2curves::bn256::Fr;
+use halo2curves::group::ff::PrimeField;
+
+impl<F: PrimeField, const T: usize, const RATE: usize> Spec<F, T, RATE> {
+    /// Hashes exactly `RATE` inputs the way circomlib does. Capacity element
+    /// starts at zero, there is no padding and the first element of the state
+    /// is returned after a single permutation
+    pub fn hash_circomlib(&self, inputs: &[F; RATE]) -> F {
+        let mut state = State::<F, T>::with_capacity(F::ZERO);
+        for (state, input) in state.0.iter_mut().skip(1).zip(inputs.iter()) {
+            *state = *input;
+        }
+        self.permute(&mut state);
+        state.0[0]
+    }
+}
+
+macro_rules! impl_circomlib_spec {
+    ($(($name:ident, $t:expr, $rate:expr)),*) => {
+        $(
+            impl Spec<Fr, $t, $rate> {
+                #[doc = concat!("circomlib compatible spec hashing ", $rate, " inputs")]
+                pub fn $name() -> Self {
+                    Self::new(
+                        <Bn256Std as PoseidonParams<Fr, $t, $rate>>::R_F,
+                        <Bn256Std as PoseidonParams<Fr, $t, $rate>>::R_P,
+                    )
+                }
+            }
+        )*
+    };
+}
+
+impl_circomlib_spec!(
+    (circomlib_t2, 2, 1),
+    (circomlib_t3, 3, 2),
+    (circomlib_t4, 4, 3),
+    (circomlib_t5, 5, 4),
+    (circomlib_t6, 6, 5),
+    (circomlib_t7, 7, 6),
+    (circomlib_t8, 8, 7),
+    (circomlib_t9, 9, 8),
+    (circomlib_t10, 10, 9),
+    (circomlib_t11, 11, 10),
+    (circomlib_t12, 12, 11),
+    (circomlib_t13, 13, 12),
+    (circomlib_t14, 14, 13),
+    (circomlib_t15, 15, 14),
+    (circomlib_t16, 16, 15),
+    (circomlib_t17, 17, 16)
+);
+
+#[cfg(test)]
+mod tests {
+    use crate::Spec;
+    use halo2curves::bn256::Fr;
+
+    fn from_dec(dec: &str) -> Fr {
+        dec.bytes().fold(Fr::zero(), |acc, d| {
+            acc * Fr::from(10) + Fr::from((d - b'0') as u64)
+        })
+    }
+
+    #[test]
+    fn test_against_circomlib() {
+        // https://github.com/iden3/circomlibjs/blob/main/test/poseidon.js
+        let spec = Spec::<Fr, 3, 2>::circomlib_t3();
+        assert_eq!(
+            spec.hash_circomlib(&[1u64, 2].map(Fr::from)),
+            from_dec(
+                "7853200120776062878684798364095072458815029376092732009249414926327459813530"
+            )
+        );
+
+        let spec = Spec::<Fr, 5, 4>::circomlib_t5();
+        assert_eq!(
+            spec.hash_circomlib(&[1u64, 2, 3, 4].map(Fr::from)),
+            from_dec(
+                "18821383157269793795438455681495246036402687001665670618754263018637548127333"
+            )
+        );
+    }
+}
@@ -0,0 +1,113 @@
+//! Errors raised while constructing or loading Poseidon parameters and
+//! absorbing inputs
+
+use std::fmt;
+
+/// `SpecError` reports why parameters can't be used to build a `Spec`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SpecError {
+    /// A matrix expected in `[row], [hat | identity]` form has an unexpected
+    /// entry in its identity block
+    NotSparse {
+        /// Row of the offending entry
+        row: usize,
+        /// Column of the offending entry
+        col: usize,
+    },
+    /// Number of matrix elements doesn't match the `T * T` shape
+    MatrixLength {
+        /// Expected number of elements
+        expected: usize,
+        /// Given number of elements
+        found: usize,
+    },
+    /// Number of round constants or number of constants in a round doesn't
+    /// match the parameters
+    ConstantLength {
+        /// Expected number of constants
+        expected: usize,
+        /// Given number of constants
+        found: usize,
+    },
+    /// There is no full round on one side of the partial rounds or full
+    /// rounds can't be halved where an even split is required. Holds the
+    /// total number of full rounds
+    FullRounds(usize),
+    /// Number of sparse matrices doesn't match number of partial rounds
+    SparseMatrixCount {
+        /// Number of partial round constants
+        expected: usize,
+        /// Given number of sparse matrices
+        found: usize,
+    },
+    /// MDS matrix is not invertible
+    SingularMds,
+    /// Transition and sparse matrices don't factorize powers of the MDS
+    /// matrix
+    Factorization,
+    /// Round constants at the given index of the optimized layout are all
+    /// zero
+    ZeroConstants(usize),
+    /// Round constants at the given index of the optimized layout repeat the
+    /// previous ones
+    RepeatedConstants(usize),
+}
+
+impl fmt::Display for SpecError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SpecError::NotSparse { row, col } => write!(
+                f,
+                "matrix is not in sparse form: unexpected entry at ({}, {})",
+                row, col
+            ),
+            SpecError::MatrixLength { expected, found } => {
+                write!(f, "matrix must have {} elements, found {}", expected, found)
+            }
+            SpecError::ConstantLength { expected, found } => {
+                write!(f, "expected {} constants, found {}", expected, found)
+            }
+            SpecError::FullRounds(r_f) => {
+                write!(
+                    f,
+                    "full rounds must be split with one on each side, found {} in total",
+                    r_f
+                )
+            }
+            SpecError::SparseMatrixCount { expected, found } => {
+                write!(f, "expected {} sparse matrices, found {}", expected, found)
+            }
+            SpecError::SingularMds => write!(f, "MDS matrix is not invertible"),
+            SpecError::Factorization => {
+                write!(f, "sparse matrices don't factorize the MDS matrix")
+            }
+            SpecError::ZeroConstants(round) => {
+                write!(f, "round constants at {} are all zero", round)
+            }
+            SpecError::RepeatedConstants(round) => {
+                write!(f, "round constants at {} repeat the previous round", round)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SpecError {}
+
+/// `InputError` reports why inputs can't be absorbed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InputError {
+    /// Bytes at the given index are not a canonical field element
+    NonCanonical(usize),
+}
+
+impl fmt::Display for InputError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InputError::NonCanonical(index) => {
+                write!(f, "input at {} is not a canonical field element", index)
+            }
+        }
+    }
+}
+
+impl std::error::Error for InputError {}
@@ -2,6 +2,36 @@ use crate::spec::MDSMatrix;
 use halo2curves::group::ff::{FromUniformBytes, PrimeField};
 use std::marker::PhantomData;
 
+/// `FieldInfo` describes a field as Grain encodes it. See [`field_info`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FieldInfo {
+    /// Bit length of the modulus which seeds the LFSR
+    pub num_bits: u32,
+    /// Length of the field element representation
+    pub num_bytes: usize,
+    /// Number of bytes that the modulus takes
+    pub modulus_bytes: usize,
+}
+
+impl FieldInfo {
+    /// Grain fills the representation with `num_bits` sampled bits so it
+    /// requires no spare bytes in the representation
+    pub fn is_grain_compatible(&self) -> bool {
+        self.modulus_bytes == self.num_bytes
+    }
+}
+
+/// Returns parameters of `F` used by Grain so that a field can be checked
+/// before constructing a `Spec`
+pub fn field_info<F: PrimeField>() -> FieldInfo {
+    let num_bits = F::NUM_BITS;
+    FieldInfo {
+        num_bits,
+        num_bytes: F::Repr::default().as_ref().len(),
+        modulus_bytes: num_bits.div_ceil(8) as usize,
+    }
+}
+
 /// Grain initializes round constants and MDS matrix at given sponge parameters
 pub(super) struct Grain<F: PrimeField, const T: usize, const RATE: usize> {
     bit_sequence: Vec<bool>,
@@ -10,16 +40,74 @@ pub(super) struct Grain<F: PrimeField, const T: usize, const RATE: usize> {
 
 impl<F: FromUniformBytes<64>, const T: usize, const RATE: usize> Grain<F, T, RATE> {
     pub(crate) fn generate(r_f: usize, r_p: usize) -> (Vec<[F; T]>, MDSMatrix<F, T, RATE>) {
+        Self::generate_with_domain(r_f, r_p, &[])
+    }
+
+    /// Generates parameters with LFSR additionally seeded with `domain` bytes.
+    /// Empty domain yields standard parameters
+    pub(crate) fn generate_with_domain(
+        r_f: usize,
+        r_p: usize,
+        domain: &[u8],
+    ) -> (Vec<[F; T]>, MDSMatrix<F, T, RATE>) {
         debug_assert!(T > 1 && T == RATE + 1);
 
+        let mut grain = Self::new(r_f, r_p, domain);
+        let constants = grain.round_constants(r_f + r_p);
+
+        let (mut xs, mut ys) = ([F::ZERO; T], [F::ZERO; T]);
+        for x in xs.iter_mut() {
+            *x = grain.next_field_element_without_rejection();
+        }
+        for y in ys.iter_mut() {
+            *y = grain.next_field_element_without_rejection();
+        }
+
+        (constants, MDSMatrix::cauchy(&xs, &ys))
+    }
+
+    /// Generates round constants with Grain while the MDS matrix is the
+    /// Cauchy matrix `1 / (x_i + y_j)` with `x_i = i` and `y_j = T + j` as
+    /// neptune does
+    pub(crate) fn generate_neptune(r_f: usize, r_p: usize) -> (Vec<[F; T]>, MDSMatrix<F, T, RATE>) {
+        debug_assert!(T > 1 && T == RATE + 1);
+
+        let mut grain = Self::new(r_f, r_p, &[]);
+        let constants = grain.round_constants(r_f + r_p);
+        let xs = std::array::from_fn(|i| F::from(i as u64));
+        let ys = std::array::from_fn(|j| F::from((T + j) as u64));
+        (constants, MDSMatrix::cauchy(&xs, &ys))
+    }
+
+    /// Samples `T` sized constants for the given number of rounds
+    fn round_constants(&mut self, number_of_rounds: usize) -> Vec<[F; T]> {
+        (0..number_of_rounds)
+            .map(|_| {
+                let mut round_constants = [F::ZERO; T];
+                for c in round_constants.iter_mut() {
+                    *c = self.next_field_element();
+                }
+                round_constants
+            })
+            .collect()
+    }
+
+    /// Initializes the LFSR with sponge parameters and `domain` bytes and
+    /// discards the first outputs
+    pub(crate) fn new(r_f: usize, r_p: usize, domain: &[u8]) -> Self {
         // Support only prime field construction
         const FIELD_TYPE: u8 = 1u8;
         // Support only \alpha s-box
         const SBOX_TYPE: u8 = 0;
 
-        let field_size = F::NUM_BITS;
-        let n_bytes = F::Repr::default().as_ref().len();
-        assert_eq!((field_size as f32 / 8.0).ceil() as usize, n_bytes);
+        let info = field_info::<F>();
+        assert!(
+            info.is_grain_compatible(),
+            "field representation of {} bytes doesn't match {} bit modulus",
+            info.num_bytes,
+            info.num_bits
+        );
+        let field_size = info.num_bits;
         assert_eq!(r_f % 2, 0);
 
         // Pseudo random number generation. See:
@@ -36,7 +124,7 @@ impl<F: FromUniformBytes<64>, const T: usize, const RATE: usize> Grain<F, T, RAT
         append_bits(&mut bit_sequence, 30, 0b111111111111111111111111111111u128);
         debug_assert_eq!(bit_sequence.len(), 80);
 
-        let mut grain: Grain<F, T, RATE> = Grain {
+        let mut grain = Grain {
             bit_sequence,
             _field: PhantomData,
         };
@@ -46,26 +134,23 @@ impl<F: FromUniformBytes<64>, const T: usize, const RATE: usize> Grain<F, T, RAT
         }
         assert_eq!(grain.bit_sequence.len(), 80);
 
-        let number_of_rounds = r_p + r_f;
-        let constants = (0..number_of_rounds)
-            .map(|_| {
-                let mut round_constants = [F::ZERO; T];
-                for c in round_constants.iter_mut() {
-                    *c = grain.next_field_element();
-                }
-                round_constants
-            })
-            .collect::<Vec<[F; T]>>();
-
-        let (mut xs, mut ys) = ([F::ZERO; T], [F::ZERO; T]);
-        for x in xs.iter_mut() {
-            *x = grain.next_field_element_without_rejection();
-        }
-        for y in ys.iter_mut() {
-            *y = grain.next_field_element_without_rejection();
+        // Feed domain bits into the state and discard another round of
+        // outputs to diffuse them
+        if !domain.is_empty() {
+            let mut domain_bits = Vec::new();
+            for byte in domain.iter() {
+                append_bits(&mut domain_bits, 8, *byte);
+            }
+            for bit in domain_bits {
+                grain.bit_sequence[0] ^= bit;
+                grain.new_bit();
+            }
+            for _ in 0..160 {
+                grain.new_bit();
+            }
         }
 
-        (constants, MDSMatrix::cauchy(&xs, &ys))
+        grain
     }
 
     /// Credit: https://github.com/zcash/halo2/tree/main/halo2_gadgets/src/primitives/poseidon
@@ -159,3 +244,23 @@ fn append_bits<T: Into<u128>>(vec: &mut Vec<bool>, n: usize, from: T) {
         vec.push((val >> i) & 1 != 0);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::field_info;
+    use halo2curves::{bn256, pasta};
+
+    #[test]
+    fn field_info_of_curves() {
+        let info = field_info::<bn256::Fr>();
+        assert_eq!(
+            (info.num_bits, info.num_bytes, info.modulus_bytes),
+            (254, 32, 32)
+        );
+        assert!(info.is_grain_compatible());
+
+        let info = field_info::<pasta::Fp>();
+        assert_eq!((info.num_bits, info.num_bytes), (255, 32));
+        assert!(info.is_grain_compatible());
+    }
+}
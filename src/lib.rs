@@ -4,11 +4,33 @@
 #![deny(missing_debug_implementations)]
 #![deny(missing_docs)]
 
+mod absorb;
+#[cfg(feature = "circomlib")]
+mod circomlib;
+mod error;
 mod grain;
 mod matrix;
+mod params;
 mod permutation;
 mod poseidon;
+mod poseidon2;
+mod security;
 mod spec;
+mod sponge;
 
-pub use crate::poseidon::Poseidon;
-pub use crate::spec::{MDSMatrices, MDSMatrix, SparseMDSMatrix, Spec, SpecRef, State};
+pub use crate::absorb::{bytes_per_element, Absorb, AbsorbableInto};
+pub use crate::error::{InputError, SpecError};
+pub use crate::grain::{field_info, FieldInfo};
+pub use crate::params::{
+    standard_rounds, Bn256Std, DefaultParams, PastaStd, PoseidonParams, SUPPORTED_WIDTHS,
+};
+pub use crate::permutation::Permutation;
+pub use crate::poseidon::{
+    ct_eq_digest, poseidon2, poseidon3, AbsorbStep, PaddingMode, Poseidon, PoseidonXof, ResultMode,
+};
+pub use crate::poseidon2::Poseidon2Spec;
+pub use crate::spec::{
+    ConstantStrategy, MDSMatrices, MDSMatrix, OptimizedConstants, RoundStructure, SparseLayout,
+    SparseMDSMatrix, Spec, SpecRef, SpecSummary, State,
+};
+pub use crate::sponge::Sponge;
@@ -81,48 +81,37 @@ impl<F: PrimeField, const T: usize> Matrix<F, T> {
         result
     }
 
-    // This is very pesky implementation of matrix inversion,
-    // It won't even alarm when a matrix is not invertable.
-    pub(crate) fn invert(&self) -> Self {
-        let identity = Self::identity();
-
-        let mut m: Vec<Vec<F>> = identity
-            .0
-            .iter()
-            .zip(self.0.iter())
-            .map(|(u_row, v_row)| {
-                let mut row = v_row.to_vec();
-                row.extend(u_row.to_vec());
-                row
-            })
-            .collect();
-
+    /// Inverts the matrix with Gauss-Jordan elimination and row pivoting.
+    /// Returns `None` if the matrix is singular
+    pub(crate) fn invert(&self) -> Option<Self> {
+        let mut m = self.0;
+        let mut res = Self::identity().0;
         for i in 0..T {
-            for j in 0..T {
-                if i != j {
-                    let r = m[j][i] * m[i][i].invert().unwrap();
-                    for k in 0..2 * T {
-                        let e = m[i][k];
-                        m[j][k] -= r * e;
-                    }
-                }
+            let pivot = (i..T).find(|&r| !bool::from(m[r][i].is_zero()))?;
+            m.swap(pivot, i);
+            res.swap(pivot, i);
+
+            let inv = m[i][i].invert().unwrap();
+            for (e, r) in m[i].iter_mut().zip(res[i].iter_mut()) {
+                *e *= inv;
+                *r *= inv;
             }
-        }
 
-        let mut res = Self::default();
-        for (i, row) in m.iter_mut().enumerate().take(T) {
-            for j in T..2 * T {
-                let e = row[i];
-                row[j] *= e.invert().unwrap()
-            }
-        }
-
-        for (i, row) in m.iter().enumerate().take(T) {
-            for j in 0..T {
-                res.set(i, j, row[j + T]);
+            let (pivot_row, pivot_res) = (m[i], res[i]);
+            for (j, (row, res_row)) in m.iter_mut().zip(res.iter_mut()).enumerate() {
+                if j == i {
+                    continue;
+                }
+                let factor = row[i];
+                for (e, p) in row.iter_mut().zip(pivot_row.iter()) {
+                    *e -= factor * p;
+                }
+                for (e, p) in res_row.iter_mut().zip(pivot_res.iter()) {
+                    *e -= factor * p;
+                }
             }
         }
-        res
+        Some(Self(res))
     }
 
     #[inline]
@@ -143,3 +132,31 @@ impl<F: PrimeField, const T: usize> Matrix<F, T> {
         Matrix::<F, RATE>::from_vec(self.0.iter().skip(1).map(|row| row[1..].to_vec()).collect())
     }
 }
+
+/// Computes the determinant of a square matrix given as rows with Gaussian
+/// elimination
+pub(crate) fn determinant<F: PrimeField>(mut m: Vec<Vec<F>>) -> F {
+    let n = m.len();
+    let mut det = F::ONE;
+    for i in 0..n {
+        let pivot = match (i..n).find(|&r| !bool::from(m[r][i].is_zero())) {
+            Some(pivot) => pivot,
+            None => return F::ZERO,
+        };
+        if pivot != i {
+            m.swap(pivot, i);
+            det = -det;
+        }
+        det *= m[i][i];
+        let inv = m[i][i].invert().unwrap();
+        let (upper, lower) = m.split_at_mut(i + 1);
+        let pivot_row = &upper[i];
+        for row in lower.iter_mut() {
+            let factor = row[i] * inv;
+            for (e, p) in row.iter_mut().zip(pivot_row.iter()).skip(i) {
+                *e -= factor * p;
+            }
+        }
+    }
+    det
+}
@@ -0,0 +1,176 @@
+//! Standard round numbers and parameter sets over BN256 and Pasta fields
+//! so that hashers can be built without passing numeric arguments
+
+use halo2curves::bn256;
+use halo2curves::group::ff::PrimeField;
+use halo2curves::pasta;
+
+/// Widths as `(T, RATE)` pairs that have standard number of rounds over BN256
+/// scalar field. See [`standard_rounds`]
+pub const SUPPORTED_WIDTHS: &[(usize, usize)] = &[
+    (2, 1),
+    (3, 2),
+    (4, 3),
+    (5, 4),
+    (6, 5),
+    (7, 6),
+    (8, 7),
+    (9, 8),
+    (10, 9),
+    (11, 10),
+    (12, 11),
+    (13, 12),
+    (14, 13),
+    (15, 14),
+    (16, 15),
+    (17, 16),
+];
+
+/// Returns standard `(r_f, r_p)` over BN256 scalar field for the width `t` as
+/// used by circomlib. Panics if `t` is not in [`SUPPORTED_WIDTHS`]
+pub const fn standard_rounds(t: usize) -> (usize, usize) {
+    let r_p = match t {
+        2 => 56,
+        3 => 57,
+        4 => 56,
+        5 => 60,
+        6 => 60,
+        7 => 63,
+        8 => 64,
+        9 => 63,
+        10 => 60,
+        11 => 66,
+        12 => 60,
+        13 => 65,
+        14 => 70,
+        15 => 60,
+        16 => 64,
+        17 => 68,
+        _ => panic!("unsupported width"),
+    };
+    (8, r_p)
+}
+
+/// `PoseidonParams` bundles number of rounds for a field and width so that
+/// a hasher can be constructed without passing numeric arguments. It is
+/// implemented for marker types, see [`Bn256Std`] and [`PastaStd`]
+pub trait PoseidonParams<F: PrimeField, const T: usize, const RATE: usize> {
+    /// Number of full rounds
+    const R_F: usize;
+    /// Number of partial rounds
+    const R_P: usize;
+}
+
+/// `DefaultParams` selects the standard parameter set of a field at the given
+/// width. It enables `Default` for [`crate::Poseidon`] so that unsupported
+/// combinations are rejected at compile time
+pub trait DefaultParams<const T: usize, const RATE: usize>: PrimeField {
+    /// Standard parameter set
+    type Params: PoseidonParams<Self, T, RATE>;
+}
+
+/// Standard parameters over BN256 scalar field as used by circomlib with
+/// widths `2..=17`
+#[derive(Debug, Clone, Copy)]
+pub struct Bn256Std;
+
+/// Standard parameters over Pallas and Vesta fields as used by halo2 with
+/// width `3`
+#[derive(Debug, Clone, Copy)]
+pub struct PastaStd;
+
+macro_rules! impl_params {
+    ($marker:ty, $field:ty, $(($t:expr, $rounds:expr)),*) => {
+        $(
+            impl PoseidonParams<$field, $t, { $t - 1 }> for $marker {
+                const R_F: usize = $rounds.0;
+                const R_P: usize = $rounds.1;
+            }
+
+            impl DefaultParams<$t, { $t - 1 }> for $field {
+                type Params = $marker;
+            }
+        )*
+    };
+}
+
+impl_params!(
+    Bn256Std,
+    bn256::Fr,
+    (2, standard_rounds(2)),
+    (3, standard_rounds(3)),
+    (4, standard_rounds(4)),
+    (5, standard_rounds(5)),
+    (6, standard_rounds(6)),
+    (7, standard_rounds(7)),
+    (8, standard_rounds(8)),
+    (9, standard_rounds(9)),
+    (10, standard_rounds(10)),
+    (11, standard_rounds(11)),
+    (12, standard_rounds(12)),
+    (13, standard_rounds(13)),
+    (14, standard_rounds(14)),
+    (15, standard_rounds(15)),
+    (16, standard_rounds(16)),
+    (17, standard_rounds(17))
+);
+impl_params!(PastaStd, pasta::Fp, (3, (8, 56)));
+impl_params!(PastaStd, pasta::Fq, (3, (8, 56)));
+
+#[cfg(test)]
+mod tests {
+    use super::{standard_rounds, Bn256Std, PastaStd, PoseidonParams, SUPPORTED_WIDTHS};
+    use crate::Poseidon;
+    use halo2curves::group::ff::Field;
+    use halo2curves::{bn256, pasta};
+    use rand_core::OsRng;
+
+    #[test]
+    fn params_match_numeric_rounds() {
+        let inputs = (0..5).map(|_| bn256::Fr::random(OsRng)).collect::<Vec<_>>();
+        let mut poseidon_0 = Poseidon::<bn256::Fr, 3, 2>::from_params::<Bn256Std>();
+        let mut poseidon_1 = Poseidon::<bn256::Fr, 3, 2>::new(8, 57);
+        poseidon_0.update(&inputs);
+        poseidon_1.update(&inputs);
+        assert_eq!(poseidon_0.squeeze(), poseidon_1.squeeze());
+
+        let mut poseidon_0 = Poseidon::<pasta::Fp, 3, 2>::from_params::<PastaStd>();
+        let mut poseidon_1 = Poseidon::<pasta::Fp, 3, 2>::new(8, 56);
+        assert_eq!(poseidon_0.squeeze(), poseidon_1.squeeze());
+    }
+
+    #[test]
+    fn supported_widths() {
+        for (t, rate) in SUPPORTED_WIDTHS.iter() {
+            assert_eq!(*t, rate + 1);
+            let (r_f, r_p) = standard_rounds(*t);
+            assert_eq!(r_f, 8);
+            assert!(r_p > 0);
+        }
+        // Standard circomlib rounds
+        let expected = [
+            56, 57, 56, 60, 60, 63, 64, 63, 60, 66, 60, 65, 70, 60, 64, 68,
+        ];
+        assert_eq!(SUPPORTED_WIDTHS.len(), expected.len());
+        for ((t, _), r_p) in SUPPORTED_WIDTHS.iter().zip(expected) {
+            assert_eq!(standard_rounds(*t), (8, r_p));
+        }
+        assert_eq!(
+            standard_rounds(3),
+            (
+                <Bn256Std as PoseidonParams<bn256::Fr, 3, 2>>::R_F,
+                <Bn256Std as PoseidonParams<bn256::Fr, 3, 2>>::R_P
+            )
+        );
+    }
+
+    #[test]
+    fn default_params() {
+        let inputs = (0..5).map(|_| bn256::Fr::random(OsRng)).collect::<Vec<_>>();
+        let mut poseidon_0 = Poseidon::<bn256::Fr, 3, 2>::default();
+        let mut poseidon_1 = Poseidon::<bn256::Fr, 3, 2>::new(8, 57);
+        poseidon_0.update(&inputs);
+        poseidon_1.update(&inputs);
+        assert_eq!(poseidon_0.squeeze(), poseidon_1.squeeze());
+    }
+}
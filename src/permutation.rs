@@ -1,50 +1,242 @@
 use halo2curves::group::ff::PrimeField;
 
-use crate::spec::{Spec, State};
+#[cfg(any(test, feature = "reference"))]
+use crate::spec::SpecRef;
+use crate::spec::{MDSMatrix, Spec, State};
+
+/// `Permutation` abstracts the permutation of a `T` sized state so that the
+/// sponge can be instantiated with permutations other than Poseidon
+pub trait Permutation<F: PrimeField, const T: usize> {
+    /// Applies the permutation to the given state
+    fn permute(&self, state: &mut State<F, T>);
+
+    /// Applies the permutation to each of the given states. Implementations
+    /// may interleave the states for throughput
+    fn permute_batch(&self, states: &mut [State<F, T>]) {
+        for state in states.iter_mut() {
+            self.permute(state);
+        }
+    }
+}
+
+impl<F: PrimeField, const T: usize, const RATE: usize> Permutation<F, T> for Spec<F, T, RATE> {
+    fn permute(&self, state: &mut State<F, T>) {
+        Spec::permute(self, state)
+    }
+
+    fn permute_batch(&self, states: &mut [State<F, T>]) {
+        Spec::permute_batch(self, states)
+    }
+}
 
 impl<F: PrimeField, const T: usize, const RATE: usize> Spec<F, T, RATE> {
     /// Applies the Poseidon permutation to the given state
     pub fn permute(&self, state: &mut State<F, T>) {
-        let r_f = self.r_f / 2;
+        self.permute_batch(std::slice::from_mut(state))
+    }
+
+    /// Applies the Poseidon permutation to each of the given states. Rounds
+    /// are interleaved across states so that round constants and matrices
+    /// are loaded once per round and independent multiplications are
+    /// adjacent
+    pub fn permute_batch(&self, states: &mut [State<F, T>]) {
+        let r_f = self.r_f_first;
 
         // First half of the full rounds
         {
-            state.add_constants(&self.constants.start[0]);
+            for state in states.iter_mut() {
+                state.add_constants(&self.constants.start[0]);
+            }
             for round_constants in self.constants.start.iter().skip(1).take(r_f - 1) {
+                for state in states.iter_mut() {
+                    state.sbox_full();
+                    state.add_constants(round_constants);
+                    self.mds_matrices.mds.apply(state);
+                }
+            }
+            let last = self.constants.start.last().unwrap();
+            for state in states.iter_mut() {
                 state.sbox_full();
-                state.add_constants(round_constants);
-                self.mds_matrices.mds.apply(state);
+                state.add_constants(last);
+                self.mds_matrices.pre_sparse_mds.apply(state)
             }
-            state.sbox_full();
-            state.add_constants(self.constants.start.last().unwrap());
-            self.mds_matrices.pre_sparse_mds.apply(state)
         }
 
-        // Partial rounds
+        // Partial rounds. Lengths agree for generated specs and are checked by
+        // `Spec::validate` for assembled ones, otherwise zipping would skip
+        // rounds
         {
+            debug_assert_eq!(
+                self.constants.partial.len(),
+                self.mds_matrices.sparse_matrices.len(),
+                "partial round constants and sparse matrices differ in length"
+            );
             for (round_constant, sparse_mds) in self
                 .constants
                 .partial
                 .iter()
                 .zip(self.mds_matrices.sparse_matrices.iter())
             {
-                state.sbox_part();
-                state.add_constant(round_constant);
-                sparse_mds.apply(state);
+                for state in states.iter_mut() {
+                    state.sbox_part();
+                    state.add_constant(round_constant);
+                    sparse_mds.apply(state);
+                }
             }
         }
 
         // Second half of the full rounds
         {
             for round_constants in self.constants.end.iter() {
+                for state in states.iter_mut() {
+                    state.sbox_full();
+                    state.add_constants(round_constants);
+                    self.mds_matrices.mds.apply(state);
+                }
+            }
+            for state in states.iter_mut() {
                 state.sbox_full();
-                state.add_constants(round_constants);
                 self.mds_matrices.mds.apply(state);
             }
-            state.sbox_full();
-            self.mds_matrices.mds.apply(state);
         }
     }
+
+    /// Applies the Poseidon permutation to a bare `T` sized array
+    ///
+    /// ```
+    /// use halo2curves::bn256::Fr;
+    /// use halo2curves::group::ff::PrimeField;
+    /// use poseidon::Spec;
+    ///
+    /// // poseidonperm_x5_254_3 of the reference test vectors
+    /// let spec = Spec::<Fr, 3, 2>::new(8, 57);
+    /// let mut words = [Fr::from(0), Fr::from(1), Fr::from(2)];
+    /// spec.permute_array(&mut words);
+    /// assert_eq!(
+    ///     words[0],
+    ///     Fr::from_str_vartime(
+    ///         "7853200120776062878684798364095072458815029376092732009249414926327459813530"
+    ///     )
+    ///     .unwrap()
+    /// );
+    /// ```
+    pub fn permute_array(&self, words: &mut [F; T]) {
+        let mut state = State(*words);
+        self.permute(&mut state);
+        *words = state.0;
+    }
+
+    /// Checks whether two specs define the same permutation regardless of
+    /// their internal representation by permuting fixed states through both.
+    /// The zero state and `[0, 1, ..., T - 1]` are permuted and the latter is
+    /// fed back for a few iterations. Agreement on these doesn't prove
+    /// equality but different parameters are detected with overwhelming
+    /// probability
+    pub fn permutation_eq(&self, other: &Self) -> bool {
+        let mut zero_0 = State([F::ZERO; T]);
+        let mut zero_1 = zero_0.clone();
+        self.permute(&mut zero_0);
+        other.permute(&mut zero_1);
+        if zero_0 != zero_1 {
+            return false;
+        }
+
+        let mut state_0 = State(std::array::from_fn(|i| F::from(i as u64)));
+        let mut state_1 = state_0.clone();
+        (0..4).all(|_| {
+            self.permute(&mut state_0);
+            other.permute(&mut state_1);
+            state_0 == state_1
+        })
+    }
+
+    /// Applies the Poseidon permutation and returns the input state followed
+    /// by the state after each full and partial round, one row per round of
+    /// an execution trace. Rounds follow the layout of
+    /// [`Spec::to_reference_constants`] so intermediate states differ from
+    /// the ones of the optimized `permute` while the final state is the same
+    pub fn permute_with_trace(&self, state: &mut State<F, T>) -> Vec<State<F, T>> {
+        let mut trace = vec![state.clone()];
+        permute_reference(
+            self.r_f_first,
+            self.constants.partial.len(),
+            &self.to_reference_constants(),
+            &self.mds_matrices.mds,
+            state,
+            |state| trace.push(state.clone()),
+        );
+        trace
+    }
+}
+
+/// Textbook round layout where every round adds `T` constants and applies
+/// the dense MDS matrix. Full rounds that follow the first `r_f_first` full
+/// and `r_p` partial rounds are the rest of `constants`. `on_round` observes
+/// the state after each round
+fn permute_reference<F: PrimeField, const T: usize, const RATE: usize>(
+    r_f_first: usize,
+    r_p: usize,
+    constants: &[[F; T]],
+    mds: &MDSMatrix<F, T, RATE>,
+    state: &mut State<F, T>,
+    mut on_round: impl FnMut(&State<F, T>),
+) {
+    let r_f = r_f_first;
+
+    for constants in constants.iter().take(r_f) {
+        state.add_constants(constants);
+        state.sbox_full();
+        mds.apply(state);
+        on_round(state);
+    }
+
+    for constants in constants.iter().skip(r_f).take(r_p) {
+        state.add_constants(constants);
+        state.sbox_part();
+        mds.apply(state);
+        on_round(state);
+    }
+
+    for constants in constants.iter().skip(r_f + r_p) {
+        state.add_constants(constants);
+        state.sbox_full();
+        mds.apply(state);
+        on_round(state);
+    }
+}
+
+#[cfg(any(test, feature = "reference"))]
+impl<F: PrimeField, const T: usize, const RATE: usize> Spec<F, T, RATE> {
+    /// Applies the Poseidon permutation without the sparse matrix
+    /// optimization. Every round adds `T` constants recovered with
+    /// [`Spec::to_reference_constants`] and applies the dense MDS matrix.
+    /// Much slower than `permute` but follows the textbook description
+    pub fn permute_naive(&self, state: &mut State<F, T>) {
+        permute_reference(
+            self.r_f_first,
+            self.constants.partial.len(),
+            &self.to_reference_constants(),
+            &self.mds_matrices.mds,
+            state,
+            |_| {},
+        );
+    }
+}
+
+#[cfg(any(test, feature = "reference"))]
+impl<F: PrimeField, const T: usize, const RATE: usize> SpecRef<F, T, RATE> {
+    /// Applies the Poseidon permutation with unoptimized constants. We want to
+    /// keep it to cross test with the optimized one
+    pub fn permute(&self, state: &mut State<F, T>) {
+        permute_reference(
+            self.r_f / 2,
+            self.r_p,
+            &self.constants,
+            &self.mds,
+            state,
+            |_| {},
+        );
+    }
 }
 
 #[cfg(test)]
@@ -54,48 +246,24 @@ mod tests {
     use halo2curves::bn256::Fr;
     use halo2curves::group::ff::PrimeField;
 
-    /// We want to keep non-optimized poseidon construction and permutation to
-    /// cross test with optimized one
-    impl<F: PrimeField, const T: usize, const RATE: usize> SpecRef<F, T, RATE> {
-        fn permute(&self, state: &mut State<F, T>) {
-            let (r_f, r_p) = (self.r_f / 2, self.r_p);
-
-            for constants in self.constants.iter().take(r_f) {
-                state.add_constants(constants);
-                state.sbox_full();
-                self.mds.apply(state);
-            }
-
-            for constants in self.constants.iter().skip(r_f).take(r_p) {
-                state.add_constants(constants);
-                state.sbox_part();
-                self.mds.apply(state);
-            }
-
-            for constants in self.constants.iter().skip(r_f + r_p) {
-                state.add_constants(constants);
-                state.sbox_full();
-                self.mds.apply(state);
-            }
-        }
-    }
-
     #[test]
     fn cross_test() {
+        use crate::standard_rounds;
         use halo2curves::group::ff::Field;
         use rand_core::OsRng;
         use std::time::Instant;
 
+        // Rounds are the standard ones of each width
         macro_rules! run_test {
             (
-                $([$RF:expr, $RP:expr, $T:expr, $RATE:expr]),*
+                $([$T:expr, $RATE:expr]),*
             ) => {
                 $(
                     {
-                        const R_F: usize = $RF;
-                        const R_P: usize = $RP;
                         const T: usize = $T;
                         const RATE: usize = $RATE;
+                        const R_F: usize = standard_rounds(T).0;
+                        const R_P: usize = standard_rounds(T).1;
                         let mut state = State(
                             (0..T)
                                 .map(|_| Fr::random(OsRng))
@@ -107,6 +275,10 @@ mod tests {
                         spec.permute(&mut state_expected);
 
                         let spec = Spec::<Fr, T, RATE>::new(R_F, R_P);
+                        let mut state_naive = state.clone();
+                        spec.permute_naive(&mut state_naive);
+                        assert_eq!(state_expected, state_naive);
+
                         let now = Instant::now();
                         {
                             spec.permute(&mut state);
@@ -118,14 +290,429 @@ mod tests {
                 )*
             };
         }
-        run_test!([8, 57, 3, 2]);
-        run_test!([8, 57, 4, 3]);
-        run_test!([8, 57, 5, 4]);
-        run_test!([8, 57, 6, 5]);
-        run_test!([8, 57, 7, 6]);
-        run_test!([8, 57, 8, 7]);
-        run_test!([8, 57, 9, 8]);
-        run_test!([8, 57, 10, 9]);
+        run_test!([3, 2]);
+        run_test!([4, 3]);
+        run_test!([5, 4]);
+        run_test!([6, 5]);
+        run_test!([7, 6]);
+        run_test!([8, 7]);
+        run_test!([9, 8]);
+        run_test!([10, 9]);
+    }
+
+    #[test]
+    fn sparse_layout() {
+        use crate::spec::{MDSMatrices, SparseMDSMatrix};
+        use halo2curves::group::ff::Field;
+        use rand_core::OsRng;
+
+        let spec = Spec::<Fr, 5, 4>::new(8, 57);
+        let layout = spec.mds_matrices().sparse_layout();
+        assert_eq!(layout.len(), 57);
+
+        // Rebuild the spec with sparse matrices taken from the layout
+        let sparse_matrices = layout
+            .rows()
+            .iter()
+            .zip(layout.col_hats().iter())
+            .map(|(row, col_hat)| {
+                SparseMDSMatrix::new(
+                    row.clone().try_into().unwrap(),
+                    col_hat.clone().try_into().unwrap(),
+                )
+            })
+            .collect();
+        let mds_matrices = MDSMatrices::new(
+            spec.mds_matrices().mds().clone(),
+            spec.mds_matrices().pre_sparse_mds().clone(),
+            sparse_matrices,
+        );
+        let rebuilt = Spec::from_parts(
+            spec.r_f_first(),
+            spec.r_f_last(),
+            mds_matrices,
+            spec.constants().clone(),
+        );
+
+        let mut state_0 = State([(); 5].map(|_| Fr::random(OsRng)));
+        let mut state_1 = state_0.clone();
+        spec.permute(&mut state_0);
+        rebuilt.permute(&mut state_1);
+        assert_eq!(state_0, state_1);
+    }
+
+    #[test]
+    fn reference_constants() {
+        use halo2curves::group::ff::Field;
+        use rand_core::OsRng;
+
+        macro_rules! run_test {
+            ($([$RF:expr, $RP:expr, $T:expr, $RATE:expr]),*) => {
+                $(
+                    let spec = Spec::<Fr, $T, $RATE>::new($RF, $RP);
+                    let constants = spec.to_reference_constants();
+                    assert_eq!(constants.len(), $RF + $RP);
+
+                    // optimize -> reference -> optimize is identity
+                    let mds = spec.mds_matrices().mds();
+                    let optimized = Spec::<Fr, $T, $RATE>::calculate_optimized_constants(
+                        $RF,
+                        $RP,
+                        constants.clone(),
+                        mds,
+                    );
+                    assert_eq!(optimized.start(), spec.constants().start());
+                    assert_eq!(optimized.partial(), spec.constants().partial());
+                    assert_eq!(optimized.end(), spec.constants().end());
+
+                    // Reference layout with recovered constants is the same
+                    // permutation
+                    let spec_ref = SpecRef::<Fr, $T, $RATE> {
+                        r_f: $RF,
+                        r_p: $RP,
+                        mds: mds.clone(),
+                        constants,
+                    };
+                    let mut state_0 = State([0; $T].map(|_| Fr::random(OsRng)));
+                    let mut state_1 = state_0.clone();
+                    spec.permute(&mut state_0);
+                    spec_ref.permute(&mut state_1);
+                    assert_eq!(state_0, state_1);
+                )*
+            };
+        }
+        run_test!([8, 57, 3, 2], [8, 60, 5, 4], [6, 31, 4, 3], [10, 57, 9, 8]);
+    }
+
+    /// Wraps `Fr` and counts multiplications done on the current thread
+    mod counted {
+        use core::iter::{Product, Sum};
+        use core::ops::{Add, AddAssign, Mul, MulAssign, Neg, Sub, SubAssign};
+        use halo2curves::bn256::Fr;
+        use halo2curves::group::ff::{Field, FromUniformBytes, PrimeField};
+        use rand_core::RngCore;
+        use std::cell::Cell;
+        use subtle::{Choice, ConditionallySelectable, ConstantTimeEq, CtOption};
+
+        thread_local! {
+            static MULTIPLICATIONS: Cell<usize> = const { Cell::new(0) };
+        }
+
+        /// Resets the counter and returns multiplications done so far
+        pub(super) fn take_count() -> usize {
+            MULTIPLICATIONS.with(|count| count.replace(0))
+        }
+
+        fn tally() {
+            MULTIPLICATIONS.with(|count| count.set(count.get() + 1));
+        }
+
+        #[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+        pub(super) struct Counted(Fr);
+
+        impl From<u64> for Counted {
+            fn from(value: u64) -> Self {
+                Counted(Fr::from(value))
+            }
+        }
+
+        impl ConditionallySelectable for Counted {
+            fn conditional_select(a: &Self, b: &Self, choice: Choice) -> Self {
+                Counted(Fr::conditional_select(&a.0, &b.0, choice))
+            }
+        }
+
+        impl ConstantTimeEq for Counted {
+            fn ct_eq(&self, other: &Self) -> Choice {
+                self.0.ct_eq(&other.0)
+            }
+        }
+
+        impl Neg for Counted {
+            type Output = Self;
+            fn neg(self) -> Self {
+                Counted(-self.0)
+            }
+        }
+
+        macro_rules! impl_op {
+            ($Op:ident, $op:ident, $OpAssign:ident, $op_assign:ident, $($tally:ident)?) => {
+                impl $Op for Counted {
+                    type Output = Self;
+                    fn $op(self, rhs: Self) -> Self {
+                        $($tally();)?
+                        Counted(self.0.$op(rhs.0))
+                    }
+                }
+                impl<'a> $Op<&'a Counted> for Counted {
+                    type Output = Self;
+                    fn $op(self, rhs: &'a Counted) -> Self {
+                        $($tally();)?
+                        Counted(self.0.$op(rhs.0))
+                    }
+                }
+                impl $OpAssign for Counted {
+                    fn $op_assign(&mut self, rhs: Self) {
+                        $($tally();)?
+                        self.0.$op_assign(rhs.0)
+                    }
+                }
+                impl<'a> $OpAssign<&'a Counted> for Counted {
+                    fn $op_assign(&mut self, rhs: &'a Counted) {
+                        $($tally();)?
+                        self.0.$op_assign(rhs.0)
+                    }
+                }
+            };
+        }
+        impl_op!(Add, add, AddAssign, add_assign,);
+        impl_op!(Sub, sub, SubAssign, sub_assign,);
+        impl_op!(Mul, mul, MulAssign, mul_assign, tally);
+
+        impl Sum for Counted {
+            fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+                iter.fold(Self::ZERO, |acc, e| acc + e)
+            }
+        }
+
+        impl<'a> Sum<&'a Counted> for Counted {
+            fn sum<I: Iterator<Item = &'a Counted>>(iter: I) -> Self {
+                iter.fold(Self::ZERO, |acc, e| acc + e)
+            }
+        }
+
+        impl Product for Counted {
+            fn product<I: Iterator<Item = Self>>(iter: I) -> Self {
+                iter.fold(Self::ONE, |acc, e| acc * e)
+            }
+        }
+
+        impl<'a> Product<&'a Counted> for Counted {
+            fn product<I: Iterator<Item = &'a Counted>>(iter: I) -> Self {
+                iter.fold(Self::ONE, |acc, e| acc * e)
+            }
+        }
+
+        impl Field for Counted {
+            const ZERO: Self = Counted(Fr::ZERO);
+            const ONE: Self = Counted(Fr::ONE);
+
+            fn random(rng: impl RngCore) -> Self {
+                Counted(Fr::random(rng))
+            }
+
+            fn square(&self) -> Self {
+                tally();
+                Counted(self.0.square())
+            }
+
+            fn double(&self) -> Self {
+                Counted(self.0.double())
+            }
+
+            fn invert(&self) -> CtOption<Self> {
+                self.0.invert().map(Counted)
+            }
+
+            fn sqrt_ratio(num: &Self, div: &Self) -> (Choice, Self) {
+                let (is_square, root) = Fr::sqrt_ratio(&num.0, &div.0);
+                (is_square, Counted(root))
+            }
+        }
+
+        impl PrimeField for Counted {
+            type Repr = <Fr as PrimeField>::Repr;
+
+            fn from_repr(repr: Self::Repr) -> CtOption<Self> {
+                Fr::from_repr(repr).map(Counted)
+            }
+
+            fn to_repr(&self) -> Self::Repr {
+                self.0.to_repr()
+            }
+
+            fn is_odd(&self) -> Choice {
+                self.0.is_odd()
+            }
+
+            const MODULUS: &'static str = Fr::MODULUS;
+            const NUM_BITS: u32 = Fr::NUM_BITS;
+            const CAPACITY: u32 = Fr::CAPACITY;
+            const TWO_INV: Self = Counted(Fr::TWO_INV);
+            const MULTIPLICATIVE_GENERATOR: Self = Counted(Fr::MULTIPLICATIVE_GENERATOR);
+            const S: u32 = Fr::S;
+            const ROOT_OF_UNITY: Self = Counted(Fr::ROOT_OF_UNITY);
+            const ROOT_OF_UNITY_INV: Self = Counted(Fr::ROOT_OF_UNITY_INV);
+            const DELTA: Self = Counted(Fr::DELTA);
+        }
+
+        impl FromUniformBytes<64> for Counted {
+            fn from_uniform_bytes(bytes: &[u8; 64]) -> Self {
+                Counted(Fr::from_uniform_bytes(bytes))
+            }
+        }
+    }
+
+    #[test]
+    fn multiplication_count() {
+        use counted::{take_count, Counted};
+
+        // Counts multiplications of an actual `permute` through the field
+        macro_rules! run_test {
+            ($([$RF:expr, $RP:expr, $T:expr, $RATE:expr]),*) => {
+                $(
+                    let spec = Spec::<Counted, $T, $RATE>::new($RF, $RP);
+                    let mut state = State(std::array::from_fn(|i| Counted::from(i as u64)));
+                    take_count();
+                    spec.permute(&mut state);
+                    assert_eq!(spec.multiplication_count(), take_count());
+                )*
+            };
+        }
+        run_test!([8, 57, 3, 2], [8, 60, 5, 4], [6, 31, 4, 3], [10, 57, 9, 8]);
+
+        let spec = Spec::<Fr, 3, 2>::new(8, 57);
+        assert_eq!(spec.multiplication_count(), 600);
+    }
+
+    #[test]
+    fn perturbed_constant_avalanche() {
+        use halo2curves::group::ff::Field;
+        use rand_core::OsRng;
+
+        let flipped_bits = |a: &State<Fr, 3>, b: &State<Fr, 3>| -> u32 {
+            a.words()
+                .iter()
+                .zip(b.words().iter())
+                .map(|(a, b)| {
+                    a.to_repr()
+                        .as_ref()
+                        .iter()
+                        .zip(b.to_repr().as_ref().iter())
+                        .map(|(a, b)| (a ^ b).count_ones())
+                        .sum::<u32>()
+                })
+                .sum()
+        };
+
+        let spec = Spec::<Fr, 3, 2>::new(8, 57);
+        let state = State([0; 3].map(|_| Fr::random(OsRng)));
+        let mut expected = state.clone();
+        spec.permute(&mut expected);
+
+        // First full round, a partial round and the last constants row
+        for (round, index) in [(0, 1), (10, 0), (64, 2)] {
+            let mut perturbed = spec.clone();
+            perturbed.perturb_constant(round, index, Fr::ONE);
+            let mut state = state.clone();
+            perturbed.permute(&mut state);
+
+            let flipped = flipped_bits(&expected, &state);
+            let total = 3 * Fr::NUM_BITS;
+            assert!(flipped > total * 35 / 100 && flipped < total * 65 / 100);
+        }
+    }
+
+    #[test]
+    fn asymmetric_full_rounds() {
+        use crate::grain::Grain;
+        use crate::RoundStructure;
+        use halo2curves::group::ff::Field;
+        use rand_core::OsRng;
+
+        // Equal halves reproduce the standard spec
+        let spec = Spec::<Fr, 3, 2>::new_with_full_rounds(4, 4, 57);
+        assert!(spec.permutation_eq(&Spec::new(8, 57)));
+        let mut state = State([0u64, 1, 2].map(Fr::from));
+        spec.permute(&mut state);
+        assert_eq!(
+            state.words()[0],
+            Fr::from_str_vartime(
+                "7853200120776062878684798364095072458815029376092732009249414926327459813530"
+            )
+            .unwrap()
+        );
+
+        let spec = Spec::<Fr, 3, 2>::new_with_full_rounds(3, 5, 57);
+        assert_eq!(spec.validate(), Ok(()));
+        assert_eq!((spec.r_f(), spec.r_f_first(), spec.r_f_last()), (8, 3, 5));
+        assert_eq!(
+            spec.round_structure(),
+            RoundStructure {
+                full_first: 3,
+                partial: 57,
+                full_last: 5
+            }
+        );
+        assert!(!spec.permutation_eq(&Spec::new(8, 57)));
+        let rebuilt = Spec::from_parts(
+            spec.r_f_first(),
+            spec.r_f_last(),
+            spec.mds_matrices().clone(),
+            spec.constants().clone(),
+        );
+        assert_eq!(rebuilt.validate(), Ok(()));
+        assert!(rebuilt.permutation_eq(&spec));
+
+        // Optimized permutation agrees with the textbook layout over the
+        // same Grain constants
+        let (constants, mds) = Grain::<Fr, 3, 2>::generate(8, 57);
+        let state = State([0; 3].map(|_| Fr::random(OsRng)));
+        let mut state_0 = state.clone();
+        super::permute_reference(3, 57, &constants, &mds, &mut state_0, |_| {});
+        let mut state_1 = state.clone();
+        spec.permute(&mut state_1);
+        assert_eq!(state_0, state_1);
+        let mut state_2 = state;
+        spec.permute_naive(&mut state_2);
+        assert_eq!(state_0, state_2);
+    }
+
+    #[test]
+    fn permutation_eq() {
+        let spec = Spec::<Fr, 3, 2>::new(8, 57);
+        let reference = spec
+            .to_reference_constants()
+            .iter()
+            .map(|row| row.to_vec())
+            .collect::<Vec<_>>();
+        let rebuilt =
+            Spec::<Fr, 3, 2>::with_constants(8, 57, &reference, spec.mds_matrices().mds().clone())
+                .unwrap();
+        assert!(spec.permutation_eq(&rebuilt));
+        assert!(rebuilt.permutation_eq(&spec));
+
+        assert!(!spec.permutation_eq(&Spec::new(8, 56)));
+        assert!(!spec.permutation_eq(&Spec::new_with_domain(8, 57, b"other")));
+    }
+
+    #[test]
+    fn permute_with_trace() {
+        use halo2curves::group::ff::Field;
+        use rand_core::OsRng;
+
+        let (r_f, r_p) = (8, 57);
+        let spec = Spec::<Fr, 3, 2>::new(r_f, r_p);
+        let input = State([0; 3].map(|_| Fr::random(OsRng)));
+
+        let mut state = input.clone();
+        let trace = spec.permute_with_trace(&mut state);
+        assert_eq!(trace.len(), r_f + r_p + 1);
+        assert_eq!(trace[0], input);
+
+        let mut expected = input;
+        spec.permute(&mut expected);
+        assert_eq!(state, expected);
+        assert_eq!(trace.last(), Some(&expected));
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "partial round constants and sparse matrices differ in length")]
+    fn permute_rejects_mismatched_partial_rounds() {
+        let mut spec = Spec::<Fr, 3, 2>::new(8, 57);
+        spec.mds_matrices.sparse_matrices.pop();
+        spec.permute(&mut State::default());
     }
 
     #[test]
@@ -204,4 +791,72 @@ mod tests {
             assert_eq!(state_0, state_1);
         }
     }
+
+    #[test]
+    fn pasta_regression_values() {
+        use halo2curves::pasta;
+
+        // Regression values for Grain generated parameters over Pallas and
+        // Vesta base fields. They are computed with this implementation, not
+        // taken from a reference source
+        macro_rules! run_test {
+            ($F:ty, [$RF:expr, $RP:expr, $T:expr, $RATE:expr], $expected:expr) => {{
+                let state = State::<$F, $T>(std::array::from_fn(|i| <$F>::from(i as u64)));
+
+                let spec_ref = SpecRef::<$F, $T, $RATE>::new($RF, $RP);
+                let mut state_0 = state.clone();
+                spec_ref.permute(&mut state_0);
+                let expected: [&str; $T] = $expected;
+                for (word, expected) in state_0.words().into_iter().zip(expected.iter()) {
+                    assert_eq!(word, <$F>::from_str_vartime(expected).unwrap());
+                }
+
+                let spec = Spec::<$F, $T, $RATE>::new($RF, $RP);
+                let mut state_1 = state;
+                spec.permute(&mut state_1);
+                assert_eq!(state_0, state_1);
+            }};
+        }
+
+        run_test!(
+            pasta::Fp,
+            [8, 56, 3, 2],
+            [
+                "19142758212910704988134549186320465225050001548607778483843514680734401733718",
+                "8943457793054409913105520643844025343653237882909500861250463986907015919658",
+                "4653491495579411712133380452970045393126868676144731347343956788496825228765",
+            ]
+        );
+        run_test!(
+            pasta::Fp,
+            [8, 60, 5, 4],
+            [
+                "7072906437237999309865720958707869648461001478194451539365469665928608448910",
+                "15910357140900815403146120202838418147950138322348554477919783506456815019990",
+                "11255758916499825187874627985315386587906196376403876253864850090689721392946",
+                "22445475121478897831722226720649817082239381778236114762965421118360408357171",
+                "24942187877351860700470694196068588099547470856072691008666375564629315564258",
+            ]
+        );
+        run_test!(
+            pasta::Fq,
+            [8, 56, 3, 2],
+            [
+                "22322561842627156685197453807735251645124552119548776724790988483233524399705",
+                "27090113248495207304570490195654932404673794912237757181609825437423660787185",
+                "17038665073773321051110301570394593864359648438432289432271269645938989965529",
+            ]
+        );
+        run_test!(
+            pasta::Fq,
+            [8, 60, 5, 4],
+            [
+                "7109938183321986694492454344867197374682801981073710024643907533736487827904",
+                "13165277847464490598479962973119952866051031494292164086128871304700352428663",
+                "26821873938384529150295175084916009429952437143500658240182723016891593100133",
+                "12555425611859437120154407890899976486166709491264504113770048288335217806651",
+                "5444103391010247019450285544693485480501294979940103806824356909736021993166",
+            ]
+        );
+    }
 }
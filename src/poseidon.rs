@@ -1,50 +1,354 @@
-use crate::{Spec, State};
+use crate::absorb::pack_bytes;
+use crate::spec::assert_width;
+use crate::{
+    Absorb, AbsorbableInto, DefaultParams, InputError, Permutation, PoseidonParams, Spec, State,
+};
 use halo2curves::group::ff::{FromUniformBytes, PrimeField};
+use std::sync::Arc;
+use subtle::{Choice, ConstantTimeEq};
+
+/// `PaddingMode` selects how absorbed inputs are finalized in `squeeze`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PaddingMode {
+    /// End of the input is marked with a single `F::ONE` element
+    #[default]
+    Variable,
+    /// In addition to the end marker total number of absorbed elements is
+    /// added to the capacity element before the final permutation. Then a
+    /// digest can't be extended by appending more inputs
+    LengthSuffix,
+}
+
+/// `ResultMode` selects how the digest is extracted from the state after the
+/// final permutation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResultMode {
+    /// Element of the state at the given index. Index `1`, the first element
+    /// of the rate part, is the default
+    Index(usize),
+    /// Sum of the rate part of the state
+    Sum,
+}
+
+impl Default for ResultMode {
+    fn default() -> Self {
+        ResultMode::Index(1)
+    }
+}
+
+impl ResultMode {
+    /// Extracts the digest from the state
+    fn extract<F: PrimeField, const T: usize>(&self, state: &State<F, T>) -> F {
+        match self {
+            ResultMode::Index(index) => state.0[*index],
+            ResultMode::Sum => state.rate_words().iter().fold(F::ZERO, |acc, e| acc + e),
+        }
+    }
+}
+
+/// `AbsorbStep` records a single addition into the state made while absorbing
+/// inputs. Steps let a circuit replicating the hasher be checked against the
+/// native one
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AbsorbStep<F> {
+    /// Index of the permutation that follows the addition
+    pub permutation: usize,
+    /// Index of the state element the value is added to
+    pub slot: usize,
+    /// Added value
+    pub value: F,
+}
 
 /// Poseidon hasher that maintains state and inputs and yields single element
 /// output when desired
+///
+/// Variable length hashing marks the end of the input with a padding element
+/// while constant length hashing of [`Poseidon::hash_fixed`] encodes the
+/// length in the capacity element instead. So the two modes yield different
+/// digests for the same input
+///
+/// The permutation defaults to the Poseidon [`Spec`] and can be replaced by
+/// any [`Permutation`] of the same width. It is shared behind an `Arc` so
+/// cloning a hasher doesn't copy constants and matrices
 #[derive(Debug, Clone)]
-pub struct Poseidon<F: PrimeField, const T: usize, const RATE: usize> {
+pub struct Poseidon<F: PrimeField, const T: usize, const RATE: usize, P = Spec<F, T, RATE>> {
     state: State<F, T>,
-    spec: Spec<F, T, RATE>,
+    spec: Arc<P>,
     absorbing: Vec<F>,
+    padding: PaddingMode,
+    result_mode: ResultMode,
+    iv: F,
+    absorbed: u64,
+    permutations: usize,
+    trace: Option<Vec<AbsorbStep<F>>>,
 }
 
 impl<F: FromUniformBytes<64>, const T: usize, const RATE: usize> Poseidon<F, T, RATE> {
     /// Constructs a clear state poseidon instance
     pub fn new(r_f: usize, r_p: usize) -> Self {
+        Self::new_with_padding(r_f, r_p, PaddingMode::default())
+    }
+
+    /// Constructs a clear state poseidon instance with number of rounds
+    /// given by the parameter set `P`
+    pub fn from_params<P: PoseidonParams<F, T, RATE>>() -> Self {
+        Self::new(P::R_F, P::R_P)
+    }
+
+    /// Constructs a clear state poseidon instance that finalizes inputs with
+    /// the given padding mode
+    pub fn new_with_padding(r_f: usize, r_p: usize, padding: PaddingMode) -> Self {
+        Self::new_with_permutation(Spec::new(r_f, r_p), padding)
+    }
+}
+
+impl<F: PrimeField, const T: usize, const RATE: usize, P: Permutation<F, T> + Clone>
+    Poseidon<F, T, RATE, P>
+{
+    /// Constructs a clear state poseidon instance over the given permutation
+    /// that finalizes inputs with the given padding mode
+    pub fn new_with_permutation(permutation: P, padding: PaddingMode) -> Self {
+        Self::from_shared(Arc::new(permutation), padding)
+    }
+
+    /// Constructs a clear state poseidon instance over a permutation shared
+    /// with other hashers, e.g. across threads
+    pub fn from_shared(spec: Arc<P>, padding: PaddingMode) -> Self {
+        assert_width::<T, RATE>();
         Self {
-            spec: Spec::new(r_f, r_p),
+            spec,
             state: State::default(),
-            absorbing: Vec::new(),
+            absorbing: Vec::with_capacity(RATE),
+            padding,
+            result_mode: ResultMode::default(),
+            iv: F::ZERO,
+            absorbed: 0,
+            permutations: 0,
+            trace: None,
+        }
+    }
+
+    /// Selects how `squeeze`, `peek` and the fixed length helpers extract the
+    /// digest from the state. Panics if the index is not in the rate part
+    /// since exposing the capacity element breaks the sponge
+    pub fn with_result_mode(mut self, result_mode: ResultMode) -> Self {
+        if let ResultMode::Index(index) = result_mode {
+            assert!(
+                (1..T).contains(&index),
+                "result index {} is not in the rate part",
+                index
+            );
+        }
+        self.result_mode = result_mode;
+        self
+    }
+
+    /// Constructs a clear state poseidon instance separated by a context
+    /// string. Context bytes are packed into elements of
+    /// [`crate::bytes_per_element`] bytes read as little-endian integers and
+    /// prefixed with the byte length. Then they are hashed in constant length
+    /// mode and the result is added to the capacity element as an IV before
+    /// any message. Helpers that start from a clear state such as
+    /// [`Poseidon::commit`] apply the IV as well. Unlike
+    /// [`Spec::new_with_domain`] constants of the permutation are unchanged
+    pub fn new_with_context(permutation: P, context: &str) -> Self {
+        let mut elements = vec![F::from(context.len() as u64)];
+        elements.extend(pack_bytes::<F>(context.as_bytes()));
+        let iv = hash_constant_length::<F, T, RATE, P>(&permutation, &elements);
+
+        let mut hasher = Self::new_with_permutation(permutation, PaddingMode::default());
+        hasher.iv = iv;
+        hasher.state.0[0] += iv;
+        hasher
+    }
+
+    /// Number of elements buffered in the absorption line awaiting a full
+    /// `RATE` sized chunk. Zero means the hasher is at a permutation boundary
+    pub fn pending(&self) -> usize {
+        self.absorbing.len()
+    }
+
+    /// Constructs a clear state instance sharing the spec, padding and result
+    /// modes and the context IV
+    fn fresh(&self) -> Self {
+        let mut state = State::default();
+        state.0[0] += self.iv;
+        Self {
+            spec: self.spec.clone(),
+            state,
+            absorbing: Vec::with_capacity(RATE),
+            padding: self.padding,
+            result_mode: self.result_mode,
+            iv: self.iv,
+            absorbed: 0,
+            permutations: 0,
+            trace: None,
         }
     }
 
     /// Appends elements to the absorption line updates state while `RATE` is
     /// full
     pub fn update(&mut self, elements: &[F]) {
-        let mut input_elements = self.absorbing.clone();
-        input_elements.extend_from_slice(elements);
-
-        for chunk in input_elements.chunks(RATE) {
-            if chunk.len() < RATE {
-                // Must be the last iteration of this update. Feed unpermutaed inputs to the
-                // absorbation line
-                self.absorbing = chunk.to_vec();
-            } else {
-                // Add new chunk of inputs for the next permutation cycle.
-                for (input_element, state) in chunk.iter().zip(self.state.0.iter_mut().skip(1)) {
-                    state.add_assign(input_element);
-                }
-                // Perform intermediate permutation
-                self.spec.permute(&mut self.state);
-                // Flush the absorption line
-                self.absorbing.clear();
-            }
+        self.absorbed += elements.len() as u64;
+
+        // Complete the pending chunk of the absorption line first
+        let pending = (RATE - self.absorbing.len()).min(elements.len());
+        let (head, elements) = elements.split_at(pending);
+        self.absorbing.extend_from_slice(head);
+        if self.absorbing.len() < RATE {
+            return;
+        }
+        let chunk = std::mem::take(&mut self.absorbing);
+        self.absorb_chunk(&chunk);
+        // Flush the absorption line keeping its allocation
+        self.absorbing = chunk;
+        self.absorbing.clear();
+
+        // Rest of the inputs are absorbed in place without buffering
+        let mut chunks = elements.chunks_exact(RATE);
+        for chunk in chunks.by_ref() {
+            self.absorb_chunk(chunk);
+        }
+        // Feed unpermutaed inputs to the absorbation line
+        self.absorbing.extend_from_slice(chunks.remainder());
+    }
+
+    /// Brings the sponge to a permutation boundary without producing output.
+    /// If the absorption line is not empty it is padded with a single
+    /// `F::ONE` followed by zeros up to `RATE` and permuted. Pad elements are
+    /// not counted as absorbed inputs. Does nothing at a boundary. Only
+    /// defined for [`PaddingMode::Variable`] and panics under other modes so
+    /// that a hash doesn't mix padding schemes
+    pub fn finish_block(&mut self) {
+        assert!(
+            self.padding == PaddingMode::Variable,
+            "finish_block requires variable length padding"
+        );
+        if self.absorbing.is_empty() {
+            return;
+        }
+        let mut chunk = std::mem::take(&mut self.absorbing);
+        chunk.push(F::ONE);
+        self.absorb_chunk(&chunk);
+        self.absorbing = chunk;
+        self.absorbing.clear();
+    }
+
+    /// Adds a full chunk of inputs to the state and performs an intermediate
+    /// permutation
+    fn absorb_chunk(&mut self, chunk: &[F]) {
+        for (slot, input_element) in chunk.iter().enumerate() {
+            self.add_to_state(slot + 1, *input_element);
+        }
+        self.permute();
+    }
+
+    /// Adds a value to the state element and records it if tracing is enabled
+    fn add_to_state(&mut self, slot: usize, value: F) {
+        self.state.0[slot].add_assign(value);
+        if let Some(trace) = self.trace.as_mut() {
+            trace.push(AbsorbStep {
+                permutation: self.permutations,
+                slot,
+                value,
+            });
+        }
+    }
+
+    /// Permutes the state and counts the permutation
+    fn permute(&mut self) {
+        self.spec.permute(&mut self.state);
+        self.permutations += 1;
+    }
+
+    /// Enables or disables recording of additions made into the state of this
+    /// hasher, e.g. by `update`, `squeeze` and the tag of `hash_to_field`.
+    /// Disabling drops already recorded steps. The context IV is added at
+    /// construction so a replay starts from the state tracing is enabled at.
+    /// Helpers such as `derive` and `commit` hash on forked hashers and
+    /// aren't recorded
+    pub fn set_trace(&mut self, enabled: bool) {
+        self.trace = enabled.then(Vec::new);
+    }
+
+    /// Returns recorded steps since tracing is enabled or the last call.
+    /// Recording continues afterwards
+    pub fn take_trace(&mut self) -> Vec<AbsorbStep<F>> {
+        self.trace.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+
+    /// Appends an integer to the absorption line as a single element
+    pub fn update_u64(&mut self, x: u64) {
+        self.update(&[F::from(x)]);
+    }
+
+    /// Appends an index or a length to the absorption line as a single
+    /// element
+    pub fn update_usize(&mut self, x: usize) {
+        self.update_u64(x as u64);
+    }
+
+    /// Appends values of a foreign type to the absorption line. Each value is
+    /// converted into one or more elements of `F`
+    pub fn update_foreign<G: AbsorbableInto<F>>(&mut self, elements: &[G]) {
+        let elements = elements
+            .iter()
+            .flat_map(|e| e.absorbable())
+            .collect::<Vec<F>>();
+        self.update(&elements);
+    }
+
+    /// Appends 32 byte chunks as field elements decoded with `from_repr`,
+    /// which is little-endian for the supported fields. Unlike the packing
+    /// of `update_foreign` non-canonical values are rejected and nothing is
+    /// absorbed then. Panics if the representation of `F` is not 32 bytes
+    pub fn update_bytes_strict(&mut self, chunks: &[[u8; 32]]) -> Result<(), InputError> {
+        assert_eq!(
+            F::Repr::default().as_ref().len(),
+            32,
+            "field representation must be 32 bytes"
+        );
+        let elements = chunks
+            .iter()
+            .enumerate()
+            .map(|(index, chunk)| {
+                let mut repr = F::Repr::default();
+                repr.as_mut().copy_from_slice(chunk);
+                Option::from(F::from_repr(repr)).ok_or(InputError::NonCanonical(index))
+            })
+            .collect::<Result<Vec<F>, _>>()?;
+        self.update(&elements);
+        Ok(())
+    }
+
+    /// Appends an optional value as a presence flag followed by the value, or
+    /// by a zero placeholder when absent. Both cases take two elements so
+    /// `None` can't collide with `Some(F::ZERO)`
+    pub fn update_option(&mut self, value: Option<F>) {
+        match value {
+            Some(value) => self.update(&[F::ONE, value]),
+            None => self.update(&[F::ZERO, F::ZERO]),
         }
     }
 
-    /// Results a single element by absorbing already added inputs
+    /// Appends a structured value to the absorption line. See [`Absorb`] for
+    /// its encoding
+    pub fn absorb<A: Absorb<F> + ?Sized>(&mut self, value: &A) {
+        value.absorb_into(self)
+    }
+
+    /// Packs bits into field elements and appends them to the absorption line.
+    /// Bit length is absorbed first so that inputs of different lengths
+    /// can't collide
+    pub fn update_bits(&mut self, bits: &[bool]) {
+        let mut elements = vec![F::from(bits.len() as u64)];
+        elements.extend(pack_bits::<F>(bits));
+        self.update(&elements);
+    }
+
+    /// Results a single element by absorbing already added inputs. Without
+    /// any inputs it is the digest of the empty input, that is the state
+    /// `[2^64, 1, 0, ...]` permuted
     pub fn squeeze(&mut self) -> F {
         let mut last_chunk = self.absorbing.clone();
         {
@@ -56,24 +360,346 @@ impl<F: FromUniformBytes<64>, const T: usize, const RATE: usize> Poseidon<F, T,
         last_chunk.push(F::ONE);
         // Add the last chunk of inputs to the state for the final permutation cycle
 
-        for (input_element, state) in last_chunk.iter().zip(self.state.0.iter_mut().skip(1)) {
-            state.add_assign(input_element);
+        for (slot, input_element) in last_chunk.iter().enumerate() {
+            self.add_to_state(slot + 1, *input_element);
+        }
+        if self.padding == PaddingMode::LengthSuffix {
+            self.add_to_state(0, F::from(self.absorbed));
         }
 
         // Perform final permutation
-        self.spec.permute(&mut self.state);
+        self.permute();
         // Flush the absorption line
         self.absorbing.clear();
         // Returns the challenge while preserving internal state
-        self.state.result()
+        self.result_mode.extract(&self.state)
+    }
+
+    /// Squeezes a digest and absorbs it back so that further inputs are bound
+    /// to it as in a Fiat-Shamir transcript
+    pub fn squeeze_and_continue(&mut self) -> F {
+        let digest = self.squeeze();
+        self.update(&[digest]);
+        digest
+    }
+
+    /// Returns the digest `squeeze` would yield without finalizing the hasher.
+    /// A copy of the hasher without the trace is squeezed so that more
+    /// inputs can be added afterwards
+    pub fn peek(&self) -> F {
+        let mut fork = self.fresh();
+        fork.state = self.state.clone();
+        fork.absorbing.extend_from_slice(&self.absorbing);
+        fork.absorbed = self.absorbed;
+        fork.squeeze()
     }
+
+    /// Same as `squeeze` but returns the canonical representation of the
+    /// digest, which is little-endian for the supported fields
+    pub fn squeeze_bytes(&mut self) -> F::Repr {
+        self.squeeze().to_repr()
+    }
+
+    /// Hashes the message into `count` elements of a field `G`, e.g. the base
+    /// field of a curve to be fed into a map-to-curve. Tag is added to the
+    /// capacity element of the current state, so inputs absorbed earlier are
+    /// bound to the outputs as well. Message and `count` are absorbed then two
+    /// elements are squeezed for each output and their representations are
+    /// concatenated into 64 bytes for `G::from_uniform_bytes`
+    pub fn hash_to_field<G: FromUniformBytes<64>>(&mut self, msg: &[F], count: usize) -> Vec<G> {
+        self.add_to_state(0, F::from_u128(State::<F, T>::HASH_TO_FIELD_TAG));
+        self.update(msg);
+        self.update(&[F::from(count as u64)]);
+        (0..count)
+            .map(|_| {
+                let mut bytes = [0u8; 64];
+                for half in bytes.chunks_mut(32) {
+                    let repr = self.squeeze().to_repr();
+                    let repr = repr.as_ref();
+                    let len = repr.len().min(32);
+                    half[..len].copy_from_slice(&repr[..len]);
+                }
+                G::from_uniform_bytes(&bytes)
+            })
+            .collect()
+    }
+
+    /// Finalizes absorbed inputs as `squeeze` does and turns the hasher into
+    /// an extendable output function. Outputs are the rate words in order
+    /// whatever the result mode is, so with the default mode first output
+    /// equals the digest `squeeze` would return
+    pub fn into_xof(mut self) -> PoseidonXof<F, T, RATE, P> {
+        self.squeeze();
+        PoseidonXof {
+            state: self.state,
+            spec: self.spec,
+            index: 0,
+        }
+    }
+
+    /// Derives a subkey for the given index from the current state. Hasher is
+    /// forked so that the master state is not disturbed
+    pub fn derive(&self, index: u64) -> F {
+        let mut fork = self.clone();
+        fork.state.0[0] += F::from_u128(State::<F, T>::DERIVE_TAG);
+        fork.update(&[F::from(index)]);
+        fork.squeeze()
+    }
+
+    /// Hashes a fixed length input in constant length mode. Capacity element
+    /// is initialized with `2^64 + (LEN - 1)` and input is padded with zeros
+    /// up to a multiple of `RATE`. State of the hasher is left untouched
+    pub fn hash_fixed<const LEN: usize>(&self, inputs: &[F; LEN]) -> F {
+        let state = permute_constant_length::<F, T, RATE, P>(&self.spec, inputs);
+        self.result_mode.extract(&state)
+    }
+
+    /// Compresses public inputs of a circuit into a single instance value.
+    /// Inputs are hashed in constant length mode so the capacity element is
+    /// keyed by the number of inputs, see [`State::constant_length_tag`].
+    /// Circuits committing to their instance are expected to match this. No
+    /// inputs hash to the permuted state keyed by a distinct tag. State of the
+    /// hasher is left untouched
+    pub fn hash_public_inputs(&self, inputs: &[F]) -> F {
+        let state = if inputs.is_empty() {
+            let mut state =
+                State::with_capacity(F::from_u128(State::<F, T>::PUBLIC_INPUTS_EMPTY_TAG));
+            self.spec.permute(&mut state);
+            state
+        } else {
+            permute_constant_length::<F, T, RATE, P>(&self.spec, inputs)
+        };
+        self.result_mode.extract(&state)
+    }
+
+    /// Hashes `N` fixed length messages as `hash_fixed` does while
+    /// interleaving their permutations with [`Permutation::permute_batch`].
+    /// State of the hasher is left untouched
+    pub fn hash_packed<const LEN: usize, const N: usize>(&self, msgs: &[[F; LEN]; N]) -> [F; N] {
+        let tag = State::<F, T>::constant_length_tag(LEN);
+        let mut states: [State<F, T>; N] = std::array::from_fn(|_| State::with_capacity(tag));
+        for offset in (0..LEN).step_by(RATE) {
+            for (state, msg) in states.iter_mut().zip(msgs.iter()) {
+                for (word, input) in state.0.iter_mut().skip(1).zip(msg[offset..].iter()) {
+                    *word += input;
+                }
+            }
+            self.spec.permute_batch(&mut states);
+        }
+        states.map(|state| self.result_mode.extract(&state))
+    }
+
+    /// Commits to `values` with the given blinding factor. Blinding is added
+    /// to the capacity element of a fresh hasher so that it is bound to every
+    /// permutation while values are absorbed as usual. State of this hasher
+    /// is left untouched
+    ///
+    /// Commitment is hiding only if `blinding` is sampled uniformly at random
+    /// and not reused
+    pub fn commit(&self, values: &[F], blinding: F) -> F {
+        let mut hasher = self.fresh();
+        hasher.state.0[0] += blinding;
+        hasher.update(values);
+        hasher.squeeze()
+    }
+
+    /// Hashes each row into a digest then hashes the digests in order. Row
+    /// and top level hashers start with distinct capacity tags so that a row
+    /// digest can't be confused with the matrix digest or a regular one. State
+    /// of this hasher is left untouched
+    pub fn hash_matrix(&self, rows: &[Vec<F>]) -> F {
+        let mut hasher = self.fresh();
+        hasher.state.0[0] += F::from_u128(State::<F, T>::MATRIX_TAG);
+        for row in rows.iter() {
+            let mut row_hasher = self.fresh();
+            row_hasher.state.0[0] += F::from_u128(State::<F, T>::MATRIX_ROW_TAG);
+            row_hasher.update(row);
+            hasher.update(&[row_hasher.squeeze()]);
+        }
+        hasher.squeeze()
+    }
+
+    /// Hashes elements as a multiset so that the digest doesn't depend on
+    /// their order. Elements are sorted by their canonical integer value and
+    /// hashed with a distinct capacity tag. Duplicates are kept so `[a, a]`
+    /// and `[a]` differ. Ordinary hashing is order dependent and must not be
+    /// used for set commitments. State of this hasher is left untouched
+    pub fn hash_commutative(&self, elements: &[F]) -> F {
+        let mut elements = elements.to_vec();
+        elements.sort_by_cached_key(|e| {
+            let mut bytes = e.to_repr().as_ref().to_vec();
+            bytes.reverse();
+            bytes
+        });
+        let mut hasher = self.fresh();
+        hasher.state.0[0] += F::from_u128(State::<F, T>::COMMUTATIVE_TAG);
+        hasher.update(&elements);
+        hasher.squeeze()
+    }
+
+    /// Hashes a sparse map given as `(index, value)` entries. Entries are
+    /// ordered by index, then number of entries followed by index and value
+    /// pairs are absorbed with a distinct capacity tag. So the digest depends
+    /// only on the map and not on the insertion order. Panics if an index is
+    /// repeated. State of this hasher is left untouched
+    pub fn hash_sparse(&self, entries: &[(u64, F)]) -> F {
+        let mut entries = entries.to_vec();
+        entries.sort_by_key(|(index, _)| *index);
+        assert!(
+            entries.windows(2).all(|pair| pair[0].0 != pair[1].0),
+            "sparse entries must have distinct indices"
+        );
+
+        let mut hasher = self.fresh();
+        hasher.state.0[0] += F::from_u128(State::<F, T>::SPARSE_TAG);
+        hasher.update_usize(entries.len());
+        for (index, value) in entries.iter() {
+            hasher.update(&[F::from(*index), *value]);
+        }
+        hasher.squeeze()
+    }
+
+    /// Hashes a sequence of variable length records. Each record is prefixed
+    /// with its length which separates it from the next one, so moving
+    /// elements across a record boundary changes the digest. Records are
+    /// absorbed with a distinct capacity tag. State of this hasher is left
+    /// untouched
+    pub fn hash_records<'a, I: IntoIterator<Item = &'a [F]>>(&self, records: I) -> F
+    where
+        F: 'a,
+    {
+        let mut hasher = self.fresh();
+        hasher.state.0[0] += F::from_u128(State::<F, T>::RECORDS_TAG);
+        for record in records {
+            hasher.update_usize(record.len());
+            hasher.update(record);
+        }
+        hasher.squeeze()
+    }
+
+    /// Absorbs the message, squeezes the tag and compares it against the
+    /// expected one in constant time. Key is expected to be absorbed before
+    /// the message
+    pub fn verify_mac(&mut self, msg: &[F], expected: F) -> Choice {
+        self.update(msg);
+        ct_eq_digest(&self.squeeze(), &expected)
+    }
+}
+
+/// `PoseidonXof` yields an unbounded stream of elements bound to the inputs
+/// absorbed by [`Poseidon::into_xof`]. Rate part of the state is output word
+/// by word and the state is permuted once it is exhausted
+#[derive(Debug, Clone)]
+pub struct PoseidonXof<F: PrimeField, const T: usize, const RATE: usize, P = Spec<F, T, RATE>> {
+    state: State<F, T>,
+    spec: Arc<P>,
+    index: usize,
+}
+
+impl<F: PrimeField, const T: usize, const RATE: usize, P: Permutation<F, T>> Iterator
+    for PoseidonXof<F, T, RATE, P>
+{
+    type Item = F;
+
+    fn next(&mut self) -> Option<F> {
+        if self.index == RATE {
+            self.spec.permute(&mut self.state);
+            self.index = 0;
+        }
+        let word = self.state.rate_words()[self.index];
+        self.index += 1;
+        Some(word)
+    }
+}
+
+impl<F, const T: usize, const RATE: usize> Default for Poseidon<F, T, RATE>
+where
+    F: FromUniformBytes<64> + DefaultParams<T, RATE>,
+{
+    /// Constructs a clear state poseidon instance with standard parameters of
+    /// the field
+    fn default() -> Self {
+        Self::from_params::<F::Params>()
+    }
+}
+
+/// Hashes inputs in constant length mode where length is part of the domain
+/// tag
+pub(crate) fn hash_constant_length<
+    F: PrimeField,
+    const T: usize,
+    const RATE: usize,
+    P: Permutation<F, T>,
+>(
+    spec: &P,
+    inputs: &[F],
+) -> F {
+    permute_constant_length::<F, T, RATE, P>(spec, inputs).result()
+}
+
+/// Absorbs inputs in constant length mode and returns the final state
+fn permute_constant_length<
+    F: PrimeField,
+    const T: usize,
+    const RATE: usize,
+    P: Permutation<F, T>,
+>(
+    spec: &P,
+    inputs: &[F],
+) -> State<F, T> {
+    assert!(!inputs.is_empty(), "constant length input can't be empty");
+    let mut state = State::<F, T>::with_capacity(State::<F, T>::constant_length_tag(inputs.len()));
+    for chunk in inputs.chunks(RATE) {
+        for (input_element, state) in chunk.iter().zip(state.0.iter_mut().skip(1)) {
+            state.add_assign(input_element);
+        }
+        spec.permute(&mut state);
+    }
+    state
+}
+
+/// Compares two digests in constant time through their canonical byte
+/// representations so that it doesn't depend on the equality of the field
+/// implementation
+pub fn ct_eq_digest<F: PrimeField>(a: &F, b: &F) -> Choice {
+    a.to_repr().as_ref().ct_eq(b.to_repr().as_ref())
+}
+
+/// Hashes two elements in constant length mode with a `T = 3` spec. Same as
+/// [`Poseidon::hash_fixed`] over `[a, b]`. Not to be confused with the
+/// Poseidon2 permutation of [`crate::Poseidon2Spec`]
+pub fn poseidon2<F: PrimeField>(spec: &Spec<F, 3, 2>, a: F, b: F) -> F {
+    hash_constant_length::<F, 3, 2, _>(spec, &[a, b])
+}
+
+/// Hashes three elements in constant length mode with a `T = 4` spec. Same
+/// as [`Poseidon::hash_fixed`] over `[a, b, c]`
+pub fn poseidon3<F: PrimeField>(spec: &Spec<F, 4, 3>, a: F, b: F, c: F) -> F {
+    hash_constant_length::<F, 4, 3, _>(spec, &[a, b, c])
+}
+
+/// Packs `F::CAPACITY` bits into each element in little-endian bit order
+fn pack_bits<F: PrimeField>(bits: &[bool]) -> Vec<F> {
+    bits.chunks(F::CAPACITY as usize)
+        .map(|chunk| {
+            chunk.iter().rev().fold(F::ZERO, |acc, bit| {
+                let acc = acc.double();
+                if *bit {
+                    acc + F::ONE
+                } else {
+                    acc
+                }
+            })
+        })
+        .collect()
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::{Poseidon, State};
+    use super::{ct_eq_digest, pack_bits, poseidon2, poseidon3};
+    use crate::{AbsorbStep, PaddingMode, Poseidon, Spec, State};
     use halo2curves::bn256::Fr;
-    use halo2curves::group::ff::Field;
+    use halo2curves::group::ff::{Field, PrimeField};
     use paste::paste;
     use rand_core::OsRng;
 
@@ -142,6 +768,859 @@ mod tests {
         assert_eq!(result_0, result_1);
     }
 
+    #[test]
+    fn poseidon_update_foreign() {
+        use crate::AbsorbableInto;
+        use halo2curves::bn256::Fq;
+
+        let base = Fq::random(OsRng);
+        let limbs: Vec<Fr> = base.absorbable();
+        assert_eq!(limbs.len(), 2);
+        // Top limb holds the last byte only
+        assert_eq!(limbs[1], Fr::from(base.to_repr().as_ref()[31] as u64));
+
+        let hash = |elements: &[Fq]| {
+            let mut poseidon = Poseidon::<Fr, T, RATE>::new(R_F, R_P);
+            poseidon.update_foreign(elements);
+            poseidon.squeeze()
+        };
+        assert_eq!(hash(&[base]), hash(&[base]));
+        assert_ne!(hash(&[base]), hash(&[base + Fq::one()]));
+
+        let mut poseidon = Poseidon::<Fr, T, RATE>::new(R_F, R_P);
+        poseidon.update(&limbs);
+        assert_eq!(hash(&[base]), poseidon.squeeze());
+    }
+
+    #[test]
+    fn poseidon_update_u64() {
+        let mut poseidon_0 = Poseidon::<Fr, T, RATE>::new(R_F, R_P);
+        poseidon_0.update_u64(5);
+        poseidon_0.update_usize(usize::MAX);
+        let mut poseidon_1 = Poseidon::<Fr, T, RATE>::new(R_F, R_P);
+        poseidon_1.update(&[Fr::from(5), Fr::from(usize::MAX as u64)]);
+        assert_eq!(poseidon_0.squeeze(), poseidon_1.squeeze());
+    }
+
+    #[test]
+    fn poseidon_update_bits() {
+        let unpack = |elements: &[Fr], len: usize| -> Vec<bool> {
+            let mut bits = vec![];
+            for e in elements.iter() {
+                let repr = e.to_repr();
+                for i in 0..Fr::CAPACITY as usize {
+                    bits.push((repr.as_ref()[i / 8] >> (i % 8)) & 1 == 1);
+                }
+            }
+            bits.truncate(len);
+            bits
+        };
+
+        for len in [0usize, 1, 7, 253, 254, 600] {
+            let bits = (0..len)
+                .map(|_| Fr::random(OsRng).is_odd().into())
+                .collect::<Vec<bool>>();
+            let packed = pack_bits::<Fr>(&bits);
+            assert_eq!(packed.len(), len.div_ceil(253));
+            assert_eq!(unpack(&packed, len), bits);
+        }
+
+        // Same packed element but different bit lengths
+        let mut poseidon_0 = Poseidon::<Fr, T, RATE>::new(R_F, R_P);
+        poseidon_0.update_bits(&[true]);
+        let mut poseidon_1 = Poseidon::<Fr, T, RATE>::new(R_F, R_P);
+        poseidon_1.update_bits(&[true, false]);
+        assert_ne!(poseidon_0.squeeze(), poseidon_1.squeeze());
+    }
+
+    #[test]
+    fn poseidon_update_large_input() {
+        let inputs = gen_random_vec(1 << 16);
+        let mut poseidon_0 = Poseidon::<Fr, T, RATE>::new(R_F, R_P);
+        poseidon_0.update(&inputs[..1]);
+        poseidon_0.update(&inputs[1..]);
+        // Full chunks are permuted as they come and only the tail is pending
+        assert_eq!(poseidon_0.pending(), inputs.len() % RATE);
+        assert_eq!(poseidon_0.permutations, inputs.len() / RATE);
+        assert!(poseidon_0.absorbing.capacity() <= RATE);
+
+        // Absorption line never grows beyond a single chunk while streaming
+        let mut poseidon_1 = poseidon_0.fresh();
+        for chunk in inputs.chunks(7) {
+            poseidon_1.update(chunk);
+            assert!(poseidon_1.absorbing.len() < RATE);
+            assert!(poseidon_1.absorbing.capacity() <= RATE);
+        }
+        assert_eq!(poseidon_0.squeeze(), poseidon_1.squeeze());
+    }
+
+    #[test]
+    fn poseidon_pending() {
+        let mut poseidon = Poseidon::<Fr, T, RATE>::new(R_F, R_P);
+        assert_eq!(poseidon.pending(), 0);
+        let mut total = 0;
+        for len in [1, RATE - 1, RATE + 2, 2 * RATE, 1] {
+            poseidon.update(&gen_random_vec(len));
+            total += len;
+            assert_eq!(poseidon.pending(), total % RATE);
+        }
+        poseidon.squeeze();
+        assert_eq!(poseidon.pending(), 0);
+    }
+
+    #[test]
+    fn poseidon_length_suffix() {
+        let message = gen_random_vec(RATE + 1);
+        let extension = gen_random_vec(3);
+
+        // Continues hashing after the first finalization
+        let extended = |padding: PaddingMode| -> Fr {
+            let mut poseidon = Poseidon::<Fr, T, RATE>::new_with_padding(R_F, R_P, padding);
+            poseidon.update(&message);
+            poseidon.squeeze();
+            poseidon.update(&extension);
+            poseidon.squeeze()
+        };
+        // Hashes the message with its padding and the extension from scratch
+        let forged = |padding: PaddingMode| -> Fr {
+            let mut inputs = message.clone();
+            inputs.push(Fr::one());
+            inputs.extend(vec![Fr::zero(); RATE - inputs.len() % RATE]);
+            inputs.extend_from_slice(&extension);
+
+            let mut poseidon = Poseidon::<Fr, T, RATE>::new_with_padding(R_F, R_P, padding);
+            poseidon.update(&inputs);
+            poseidon.squeeze()
+        };
+
+        assert_eq!(
+            extended(PaddingMode::Variable),
+            forged(PaddingMode::Variable)
+        );
+        assert_ne!(
+            extended(PaddingMode::LengthSuffix),
+            forged(PaddingMode::LengthSuffix)
+        );
+    }
+
+    #[test]
+    fn poseidon_peek() {
+        for padding in [PaddingMode::Variable, PaddingMode::LengthSuffix] {
+            let inputs = gen_random_vec(RATE + 3);
+            let mut poseidon = Poseidon::<Fr, T, RATE>::new_with_padding(R_F, R_P, padding);
+            poseidon.update(&inputs[..RATE + 1]);
+            let peeked = poseidon.peek();
+            assert_eq!(peeked, poseidon.peek());
+            assert_eq!(peeked, poseidon.clone().squeeze());
+
+            // Hasher keeps absorbing after peeking as if it never happened
+            poseidon.update(&inputs[RATE + 1..]);
+            let result = poseidon.squeeze();
+            assert_ne!(result, peeked);
+            let mut expected = Poseidon::<Fr, T, RATE>::new_with_padding(R_F, R_P, padding);
+            expected.update(&inputs);
+            assert_eq!(result, expected.squeeze());
+        }
+    }
+
+    #[test]
+    fn poseidon_xof() {
+        let mut poseidon = Poseidon::<Fr, T, RATE>::new(R_F, R_P);
+        poseidon.update(&gen_random_vec(RATE + 1));
+        let spec = poseidon.spec.clone();
+
+        let mut finalized = poseidon.clone();
+        let digest = finalized.squeeze();
+        let outputs = poseidon.into_xof().take(2 * RATE + 1).collect::<Vec<_>>();
+        assert_eq!(outputs[0], digest);
+
+        let mut state = finalized.state;
+        let mut expected = vec![];
+        for _ in 0..3 {
+            expected.extend_from_slice(state.rate_words());
+            spec.permute(&mut state);
+        }
+        assert_eq!(outputs[..], expected[..2 * RATE + 1]);
+    }
+
+    #[test]
+    fn poseidon_derive() {
+        let mut master = Poseidon::<Fr, T, RATE>::new(R_F, R_P);
+        master.update(&gen_random_vec(3));
+        let snapshot = master.clone();
+
+        let keys = (0..8).map(|i| master.derive(i)).collect::<Vec<Fr>>();
+        for (i, key) in keys.iter().enumerate() {
+            assert_eq!(*key, master.derive(i as u64));
+            assert!(keys.iter().skip(i + 1).all(|other| other != key));
+        }
+
+        // Derivation doesn't collide with absorbing the index
+        let mut fork = master.clone();
+        fork.update(&[Fr::from(0)]);
+        assert_ne!(fork.squeeze(), keys[0]);
+
+        assert_eq!(master.state, snapshot.state);
+        assert_eq!(master.absorbing, snapshot.absorbing);
+    }
+
+    #[test]
+    fn poseidon_hash_fixed() {
+        let poseidon = Poseidon::<Fr, 3, 2>::new(R_F, R_P);
+        let inputs = [0; 3].map(|_| Fr::random(OsRng));
+        let result_0 = poseidon.hash_fixed(&inputs);
+
+        let mut state = State::<Fr, 3>::default();
+        state.0[0] = Fr::from_u128((1 << 64) + 2);
+        for chunk in [
+            [Fr::zero(), inputs[0], inputs[1]],
+            [Fr::zero(), inputs[2], Fr::zero()],
+        ] {
+            state.add_constants(&chunk);
+            poseidon.spec.permute(&mut state);
+        }
+        assert_eq!(result_0, state.result());
+
+        let mut poseidon = poseidon;
+        poseidon.update(&inputs);
+        assert_ne!(result_0, poseidon.squeeze());
+    }
+
+    #[test]
+    fn poseidon_commit() {
+        let mut poseidon = Poseidon::<Fr, T, RATE>::new(R_F, R_P);
+        poseidon.update(&gen_random_vec(3));
+        let snapshot = poseidon.clone();
+
+        let values = gen_random_vec(RATE + 1);
+        let blinding = Fr::random(OsRng);
+        let commitment = poseidon.commit(&values, blinding);
+
+        // Opening with the same values and blinding reproduces the commitment
+        assert_eq!(commitment, snapshot.commit(&values, blinding));
+        assert_ne!(commitment, poseidon.commit(&values, Fr::random(OsRng)));
+        assert_ne!(commitment, poseidon.commit(&values[1..], blinding));
+
+        let mut state = State::<Fr, T>::default();
+        state.0[0] += blinding;
+        let mut inputs = values.clone();
+        inputs.push(Fr::one());
+        inputs.extend(vec![Fr::zero(); RATE - inputs.len() % RATE]);
+        for chunk in inputs.chunks(RATE) {
+            for (state, input) in state.0.iter_mut().skip(1).zip(chunk.iter()) {
+                *state += input;
+            }
+            poseidon.spec.permute(&mut state);
+        }
+        assert_eq!(commitment, state.result());
+
+        assert_eq!(poseidon.state, snapshot.state);
+        assert_eq!(poseidon.absorbing, snapshot.absorbing);
+    }
+
+    #[test]
+    fn poseidon_hash_to_field() {
+        use halo2curves::bn256::Fq;
+        use halo2curves::group::ff::FromUniformBytes;
+
+        let poseidon = Poseidon::<Fr, T, RATE>::new(R_F, R_P);
+        let hash = |msg: &[Fr], count: usize| poseidon.clone().hash_to_field::<Fq>(msg, count);
+        let msg = gen_random_vec(3);
+        let outputs = hash(&msg, 3);
+        assert_eq!(outputs.len(), 3);
+        assert_eq!(outputs, hash(&msg, 3));
+        // Number of outputs is bound to the digest
+        assert_ne!(hash(&msg, 2)[..], outputs[..2]);
+        // Inputs absorbed before are bound as well
+        let mut primed = poseidon.clone();
+        primed.update(&[Fr::one()]);
+        assert_ne!(primed.hash_to_field::<Fq>(&msg, 3), outputs);
+
+        // Tag separates outputs from plain hashing of `msg || count`
+        let mut plain = poseidon.clone();
+        plain.update(&msg);
+        plain.update(&[Fr::from(3)]);
+        let mut bytes = [0u8; 64];
+        for half in bytes.chunks_mut(32) {
+            half.copy_from_slice(plain.squeeze().to_repr().as_ref());
+        }
+        assert_ne!(outputs[0], Fq::from_uniform_bytes(&bytes));
+
+        // Lowest four bits of outputs are expected to be uniform
+        let samples = 4096;
+        let mut buckets = [0usize; 16];
+        for _ in 0..samples / 2 {
+            for output in hash(&gen_random_vec(2), 2) {
+                buckets[(output.to_repr().as_ref()[0] & 0x0f) as usize] += 1;
+            }
+        }
+        let expected = samples as f64 / 16.0;
+        let chi_square = buckets
+            .iter()
+            .map(|count| (*count as f64 - expected).powi(2) / expected)
+            .sum::<f64>();
+        // Critical value for 15 degrees of freedom at p = 0.00001
+        assert!(chi_square < 48.0, "chi square {}", chi_square);
+    }
+
+    #[test]
+    fn poseidon_small_tuples() {
+        let [a, b, c] = [0; 3].map(|_| Fr::random(OsRng));
+
+        let poseidon = Poseidon::<Fr, 3, 2>::new(R_F, R_P);
+        assert_eq!(
+            poseidon2(&poseidon.spec, a, b),
+            poseidon.hash_fixed(&[a, b])
+        );
+        assert_ne!(
+            poseidon2(&poseidon.spec, a, b),
+            poseidon2(&poseidon.spec, b, a)
+        );
+
+        let poseidon = Poseidon::<Fr, 4, 3>::new(R_F, R_P);
+        assert_eq!(
+            poseidon3(&poseidon.spec, a, b, c),
+            poseidon.hash_fixed(&[a, b, c])
+        );
+    }
+
+    #[test]
+    fn poseidon_hash_matrix() {
+        let poseidon = Poseidon::<Fr, T, RATE>::new(R_F, R_P);
+        let rows = (0..3).map(|i| gen_random_vec(i + 2)).collect::<Vec<_>>();
+        let digest = poseidon.hash_matrix(&rows);
+        assert_eq!(digest, poseidon.hash_matrix(&rows));
+
+        let mut reordered = rows.clone();
+        reordered.swap(0, 2);
+        assert_ne!(digest, poseidon.hash_matrix(&reordered));
+
+        // Single row matrix doesn't collide with hashing the row itself
+        let mut hasher = poseidon.clone();
+        hasher.update(&rows[0]);
+        assert_ne!(poseidon.hash_matrix(&rows[..1]), hasher.squeeze());
+    }
+
+    #[test]
+    fn poseidon_ct_eq_digest() {
+        for _ in 0..1000 {
+            let a = Fr::random(OsRng);
+            let b = if bool::from(a.is_odd()) {
+                a
+            } else {
+                Fr::random(OsRng)
+            };
+            assert_eq!(bool::from(ct_eq_digest(&a, &b)), a == b);
+            assert!(bool::from(ct_eq_digest(&a, &a)));
+            assert!(!bool::from(ct_eq_digest(&a, &(a + Fr::one()))));
+        }
+
+        // Digests differing only in the first or only in the last byte
+        let mut repr = Fr::random(OsRng).to_repr();
+        repr.as_mut()[31] = 0;
+        let a = Fr::from_repr(repr).unwrap();
+        for index in [0, 31] {
+            let mut repr = repr;
+            repr.as_mut()[index] ^= 1;
+            let b = Fr::from_repr(repr).unwrap();
+            assert!(!bool::from(ct_eq_digest(&a, &b)));
+            assert!(!bool::from(ct_eq_digest(&b, &a)));
+        }
+    }
+
+    #[test]
+    fn poseidon_custom_permutation() {
+        use crate::Permutation;
+
+        #[derive(Debug, Clone)]
+        struct Identity;
+
+        impl Permutation<Fr, 3> for Identity {
+            fn permute(&self, _: &mut State<Fr, 3>) {}
+        }
+
+        let [a, b, c] = [0; 3].map(|_| Fr::random(OsRng));
+        let mut poseidon =
+            Poseidon::<Fr, 3, 2, Identity>::new_with_permutation(Identity, PaddingMode::Variable);
+        poseidon.update(&[a, b, c]);
+        // Second chunk `[c, 1]` is added on top of the first one
+        assert_eq!(poseidon.squeeze(), a + c);
+        assert_eq!(poseidon.permutations, 2);
+    }
+
+    #[test]
+    fn poseidon_new_with_context() {
+        let spec = Spec::<Fr, T, RATE>::new(R_F, R_P);
+        let msg = gen_random_vec(RATE + 1);
+        let hash = |context: &str| {
+            let mut poseidon = Poseidon::<Fr, T, RATE>::new_with_context(spec.clone(), context);
+            poseidon.update(&msg);
+            poseidon.squeeze()
+        };
+
+        let digest = hash("MyApp-v1-leaf");
+        assert_eq!(digest, hash("MyApp-v1-leaf"));
+        assert_ne!(digest, hash("MyApp-v1-node"));
+        // Trailing zero bytes are bound by the length prefix
+        assert_ne!(hash(""), hash("\0"));
+
+        let mut poseidon = Poseidon::<Fr, T, RATE>::new(R_F, R_P);
+        poseidon.update(&msg);
+        assert_ne!(hash(""), poseidon.squeeze());
+
+        // Helpers starting from a clear state keep the context
+        let plain = Poseidon::<Fr, T, RATE>::new(R_F, R_P);
+        let leaf = Poseidon::<Fr, T, RATE>::new_with_context(spec.clone(), "MyApp-v1-leaf");
+        let mut expected = leaf.clone();
+        expected.update(&msg);
+        assert_eq!(leaf.commit(&msg, Fr::zero()), expected.squeeze());
+        assert_ne!(
+            leaf.commit(&msg, Fr::zero()),
+            plain.commit(&msg, Fr::zero())
+        );
+        assert_ne!(
+            leaf.hash_matrix(std::slice::from_ref(&msg)),
+            plain.hash_matrix(std::slice::from_ref(&msg))
+        );
+        assert_ne!(leaf.hash_commutative(&msg), plain.hash_commutative(&msg));
+        assert_ne!(
+            leaf.hash_sparse(&[(1, msg[0])]),
+            plain.hash_sparse(&[(1, msg[0])])
+        );
+        assert_ne!(
+            leaf.hash_records([&msg[..]]),
+            plain.hash_records([&msg[..]])
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "width mismatch: T = 4 must be RATE + 1 = 3")]
+    fn poseidon_width_mismatch() {
+        Poseidon::<Fr, 4, 2>::new(R_F, R_P);
+    }
+
+    #[test]
+    fn poseidon_hash_commutative() {
+        let poseidon = Poseidon::<Fr, T, RATE>::new(R_F, R_P);
+        let elements = gen_random_vec(RATE + 2);
+        let mut permuted = elements.clone();
+        permuted.rotate_left(2);
+        permuted.swap(0, 1);
+
+        let digest = poseidon.hash_commutative(&elements);
+        assert_eq!(digest, poseidon.hash_commutative(&permuted));
+        assert_ne!(digest, poseidon.hash_commutative(&elements[1..]));
+        assert_ne!(
+            poseidon.hash_commutative(&elements[..1]),
+            poseidon.hash_commutative(&[elements[0], elements[0]])
+        );
+
+        let hash = |elements: &[Fr]| {
+            let mut hasher = poseidon.clone();
+            hasher.update(elements);
+            hasher.squeeze()
+        };
+        assert_ne!(hash(&elements), hash(&permuted));
+    }
+
+    #[test]
+    fn poseidon_hash_sparse() {
+        let poseidon = Poseidon::<Fr, T, RATE>::new(R_F, R_P);
+        let values = gen_random_vec(3);
+        let entries = [(7, values[0]), (2, values[1]), (1 << 40, values[2])];
+        let mut reordered = entries;
+        reordered.reverse();
+
+        let digest = poseidon.hash_sparse(&entries);
+        assert_eq!(digest, poseidon.hash_sparse(&reordered));
+        assert_ne!(digest, poseidon.hash_sparse(&entries[1..]));
+        assert_ne!(
+            digest,
+            poseidon.hash_sparse(&[(8, values[0]), (2, values[1]), (1 << 40, values[2])])
+        );
+        assert_ne!(
+            digest,
+            poseidon.hash_sparse(&[(7, values[1]), (2, values[0]), (1 << 40, values[2])])
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "sparse entries must have distinct indices")]
+    fn poseidon_hash_sparse_repeated_index() {
+        let poseidon = Poseidon::<Fr, T, RATE>::new(R_F, R_P);
+        poseidon.hash_sparse(&[(1, Fr::one()), (1, Fr::zero())]);
+    }
+
+    #[test]
+    fn poseidon_shared_spec() {
+        use std::sync::Arc;
+
+        let template = Poseidon::<Fr, T, RATE>::new(R_F, R_P);
+        let mut fork = template.clone();
+        assert!(Arc::ptr_eq(&template.spec, &fork.spec));
+
+        let inputs = gen_random_vec(RATE + 1);
+        let mut shared =
+            Poseidon::<Fr, T, RATE>::from_shared(template.spec.clone(), PaddingMode::Variable);
+        shared.update(&inputs);
+        fork.update(&inputs);
+        assert_eq!(shared.squeeze(), fork.squeeze());
+    }
+
+    #[test]
+    fn poseidon_squeeze_and_continue() {
+        let inputs = gen_random_vec(3);
+        let mut poseidon = Poseidon::<Fr, T, RATE>::new(R_F, R_P);
+        let mut plain = poseidon.clone();
+
+        poseidon.update(&inputs[..2]);
+        let challenge_0 = poseidon.squeeze_and_continue();
+        poseidon.update(&inputs[2..]);
+        let challenge_1 = poseidon.squeeze_and_continue();
+
+        plain.update(&inputs[..2]);
+        assert_eq!(plain.clone().squeeze(), challenge_0);
+        let mut expected = plain.clone();
+        expected.squeeze();
+        expected.update(&[challenge_0, inputs[2]]);
+        assert_eq!(expected.squeeze(), challenge_1);
+
+        // Plain squeezes are not bound to the previous challenge
+        plain.squeeze();
+        plain.update(&inputs[2..]);
+        assert_ne!(plain.squeeze(), challenge_1);
+    }
+
+    #[test]
+    fn poseidon_hash_records() {
+        let poseidon = Poseidon::<Fr, T, RATE>::new(R_F, R_P);
+        let [a, b, c] = [0; 3].map(|_| Fr::random(OsRng));
+
+        let digest = poseidon.hash_records([&[a][..], &[b, c]]);
+        assert_ne!(digest, poseidon.hash_records([&[a, b][..], &[c]]));
+        assert_ne!(digest, poseidon.hash_records([&[a, b, c][..]]));
+        assert_eq!(
+            digest,
+            poseidon.hash_records([vec![a], vec![b, c]].iter().map(Vec::as_slice))
+        );
+
+        // Empty records still count
+        let empty: &[Fr] = &[];
+        assert_ne!(
+            poseidon.hash_records([empty]),
+            poseidon.hash_records(std::iter::empty())
+        );
+        // Distinct from hashing the encoding directly
+        let mut hasher = poseidon.clone();
+        hasher.update(&[Fr::one(), a, Fr::from(2), b, c]);
+        assert_ne!(digest, hasher.squeeze());
+    }
+
+    #[test]
+    fn poseidon_finish_block() {
+        let inputs = gen_random_vec(RATE + 1);
+        let mut poseidon = Poseidon::<Fr, T, RATE>::new(R_F, R_P);
+        poseidon.update(&inputs);
+        let mut direct = poseidon.clone();
+
+        poseidon.finish_block();
+        assert_eq!(poseidon.pending(), 0);
+        assert_eq!(poseidon.permutations, 2);
+        // At a boundary it is a no-op
+        let mut again = poseidon.clone();
+        again.finish_block();
+        assert_eq!(again.state, poseidon.state);
+
+        // Same as absorbing the pad explicitly
+        let mut expected = direct.clone();
+        expected.update(&[Fr::one(), Fr::zero(), Fr::zero()]);
+        assert_eq!(expected.state, poseidon.state);
+
+        let digest = poseidon.squeeze();
+        assert_eq!(digest, expected.squeeze());
+        assert_ne!(digest, direct.squeeze());
+    }
+
+    #[test]
+    #[should_panic(expected = "finish_block requires variable length padding")]
+    fn poseidon_finish_block_rejects_length_suffix() {
+        let mut poseidon =
+            Poseidon::<Fr, T, RATE>::new_with_padding(R_F, R_P, PaddingMode::LengthSuffix);
+        poseidon.update(&gen_random_vec(1));
+        poseidon.finish_block();
+    }
+
+    #[test]
+    fn poseidon_hash_public_inputs() {
+        let poseidon = Poseidon::<Fr, 3, 2>::new(8, 57);
+        let inputs = [1u64, 2, 3].map(Fr::from);
+        let digest = poseidon.hash_public_inputs(&inputs);
+        assert_eq!(
+            digest,
+            Fr::from_str_vartime(
+                "11669233072113462387990816372081442198885999671504251452533729894512255606317"
+            )
+            .unwrap()
+        );
+        assert_eq!(digest, poseidon.hash_fixed(&inputs));
+        assert_ne!(digest, poseidon.hash_public_inputs(&inputs[..2]));
+
+        // No inputs is the tagged state permuted once
+        let mut state =
+            State::with_capacity(Fr::from_u128(State::<Fr, 3>::PUBLIC_INPUTS_EMPTY_TAG));
+        Spec::<Fr, 3, 2>::new(8, 57).permute(&mut state);
+        let empty = poseidon.hash_public_inputs(&[]);
+        assert_eq!(empty, state.result());
+        assert_ne!(empty, poseidon.hash_public_inputs(&[Fr::zero()]));
+        assert_ne!(empty, Poseidon::<Fr, 3, 2>::new(8, 57).squeeze());
+    }
+
+    #[test]
+    fn poseidon_empty_input() {
+        let mut poseidon = Poseidon::<Fr, 3, 2>::new(8, 57);
+        let digest = poseidon.squeeze();
+        assert_eq!(
+            digest,
+            Fr::from_str_vartime(
+                "9362338198138338175086986366978400070207062939342763846442323463227805666726"
+            )
+            .unwrap()
+        );
+
+        let spec = Spec::<Fr, 3, 2>::new(8, 57);
+        let mut state = State::default();
+        state.0[1] = Fr::one();
+        spec.permute(&mut state);
+        assert_eq!(digest, state.result());
+    }
+
+    #[test]
+    fn poseidon_hash_packed() {
+        let poseidon = Poseidon::<Fr, T, RATE>::new(R_F, R_P);
+        let msgs = [0; 4].map(|_| [0; 5].map(|_| Fr::random(OsRng)));
+        let digests = poseidon.hash_packed(&msgs);
+        for (msg, digest) in msgs.iter().zip(digests.iter()) {
+            assert_eq!(poseidon.hash_fixed(msg), *digest);
+        }
+
+        // Default batching of other permutations agrees as well
+        let poseidon = Poseidon::<Fr, 3, 2, _>::new_with_permutation(
+            crate::Poseidon2Spec::<Fr, 3, 2>::new(8, 56),
+            PaddingMode::Variable,
+        );
+        let msgs = [0; 3].map(|_| [0; 2].map(|_| Fr::random(OsRng)));
+        let digests = poseidon.hash_packed(&msgs);
+        for (msg, digest) in msgs.iter().zip(digests.iter()) {
+            assert_eq!(poseidon.hash_fixed(msg), *digest);
+        }
+    }
+
+    #[test]
+    fn poseidon_update_bytes_strict() {
+        use crate::InputError;
+
+        let inputs = gen_random_vec(3);
+        let chunks = inputs
+            .iter()
+            .map(|e| e.to_repr().as_ref().try_into().unwrap())
+            .collect::<Vec<[u8; 32]>>();
+        let mut poseidon_0 = Poseidon::<Fr, T, RATE>::new(R_F, R_P);
+        let mut poseidon_1 = poseidon_0.clone();
+        poseidon_0.update_bytes_strict(&chunks).unwrap();
+        poseidon_1.update(&inputs);
+        assert_eq!(poseidon_0.peek(), poseidon_1.peek());
+
+        // Modulus is `-1 + 1` where the lowest byte of `-1` is zero
+        let mut modulus: [u8; 32] = (-Fr::one()).to_repr().as_ref().try_into().unwrap();
+        modulus[0] += 1;
+        let mut poseidon = poseidon_1.clone();
+        assert_eq!(
+            poseidon.update_bytes_strict(&[chunks[0], modulus]),
+            Err(InputError::NonCanonical(1))
+        );
+        assert_eq!(poseidon.pending(), poseidon_1.pending());
+        assert_eq!(poseidon.squeeze(), poseidon_1.squeeze());
+    }
+
+    #[test]
+    fn poseidon_update_option() {
+        let hash = |values: &[Option<Fr>]| {
+            let mut poseidon = Poseidon::<Fr, T, RATE>::new(R_F, R_P);
+            for value in values.iter() {
+                poseidon.update_option(*value);
+            }
+            poseidon.squeeze()
+        };
+
+        assert_ne!(hash(&[Some(Fr::zero())]), hash(&[None]));
+        assert_ne!(
+            hash(&[None, Some(Fr::one())]),
+            hash(&[Some(Fr::one()), None])
+        );
+        // Presence flag and value are absorbed in order
+        let value = Fr::random(OsRng);
+        let mut poseidon = Poseidon::<Fr, T, RATE>::new(R_F, R_P);
+        poseidon.update(&[Fr::one(), value]);
+        assert_eq!(hash(&[Some(value)]), poseidon.squeeze());
+    }
+
+    #[test]
+    fn poseidon_squeeze_bytes() {
+        let inputs = gen_random_vec(RATE + 1);
+        let mut poseidon = Poseidon::<Fr, T, RATE>::new(R_F, R_P);
+        poseidon.update(&inputs);
+        let mut expected = poseidon.clone();
+
+        let bytes = poseidon.squeeze_bytes();
+        assert_eq!(bytes.as_ref().len(), 32);
+        assert_eq!(Fr::from_repr(bytes).unwrap(), expected.squeeze());
+        // Subsequent outputs stay in sync
+        assert_eq!(
+            Fr::from_repr(poseidon.squeeze_bytes()).unwrap(),
+            expected.squeeze()
+        );
+    }
+
+    #[test]
+    fn poseidon_result_mode() {
+        use crate::ResultMode;
+
+        let inputs = gen_random_vec(RATE + 1);
+        let hash = |mode: ResultMode| {
+            let mut poseidon = Poseidon::<Fr, T, RATE>::new(R_F, R_P).with_result_mode(mode);
+            poseidon.update(&inputs);
+            assert_eq!(poseidon.peek(), poseidon.clone().squeeze());
+            (poseidon.squeeze(), poseidon.state)
+        };
+
+        let (default, state) = hash(ResultMode::default());
+        assert_eq!(default, state.0[1]);
+        assert_eq!(hash(ResultMode::Index(1)).0, default);
+        assert_eq!(hash(ResultMode::Index(T - 1)).0, state.0[T - 1]);
+
+        let (sum, _) = hash(ResultMode::Sum);
+        assert_eq!(sum, state.0[1..].iter().fold(Fr::zero(), |acc, e| acc + e));
+        assert_ne!(sum, default);
+
+        // Fixed length helpers follow the mode as well
+        let msg = [Fr::one(), Fr::from(2)];
+        let poseidon = Poseidon::<Fr, T, RATE>::new(R_F, R_P);
+        let mut state = State::with_capacity(State::<Fr, T>::constant_length_tag(2));
+        state.0[1] = msg[0];
+        state.0[2] = msg[1];
+        poseidon.spec.permute(&mut state);
+        let poseidon = poseidon.with_result_mode(ResultMode::Sum);
+        let expected = state.rate_words().iter().fold(Fr::zero(), |acc, e| acc + e);
+        assert_eq!(poseidon.hash_fixed(&msg), expected);
+        assert_eq!(poseidon.hash_packed(&[msg]), [expected]);
+    }
+
+    #[test]
+    #[should_panic(expected = "result index 0 is not in the rate part")]
+    fn poseidon_result_mode_rejects_capacity() {
+        use crate::ResultMode;
+
+        Poseidon::<Fr, T, RATE>::new(R_F, R_P).with_result_mode(ResultMode::Index(0));
+    }
+
+    #[test]
+    fn poseidon_verify_mac() {
+        let key = gen_random_vec(2);
+        let msg = gen_random_vec(5);
+        let keyed = || {
+            let mut poseidon = Poseidon::<Fr, T, RATE>::new(R_F, R_P);
+            poseidon.update(&key);
+            poseidon
+        };
+
+        let mut poseidon = keyed();
+        poseidon.update(&msg);
+        let tag = poseidon.squeeze();
+
+        assert!(bool::from(keyed().verify_mac(&msg, tag)));
+        assert!(!bool::from(keyed().verify_mac(&msg, tag + Fr::one())));
+        assert!(!bool::from(keyed().verify_mac(&msg, -tag)));
+        assert!(!bool::from(keyed().verify_mac(&msg[1..], tag)));
+    }
+
+    #[test]
+    fn poseidon_trace() {
+        let inputs = gen_random_vec(2 * RATE + 1);
+        let mut poseidon = Poseidon::<Fr, T, RATE>::new(R_F, R_P);
+        poseidon.update(&inputs[..1]);
+        // Buffered input is recorded once it is added into the state
+        poseidon.set_trace(true);
+        poseidon.update(&inputs[1..]);
+        let result = poseidon.squeeze();
+        let trace = poseidon.take_trace();
+        assert!(poseidon.take_trace().is_empty());
+
+        let mut padded = inputs.clone();
+        padded.push(Fr::one());
+        let expected = padded
+            .iter()
+            .enumerate()
+            .map(|(i, value)| AbsorbStep {
+                permutation: i / RATE,
+                slot: i % RATE + 1,
+                value: *value,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(trace, expected);
+
+        let mut state = State::<Fr, T>::default();
+        let mut permutation = 0;
+        for step in trace.iter() {
+            while permutation < step.permutation {
+                poseidon.spec.permute(&mut state);
+                permutation += 1;
+            }
+            state.0[step.slot] += step.value;
+        }
+        poseidon.spec.permute(&mut state);
+        assert_eq!(state.result(), result);
+
+        poseidon.set_trace(false);
+        poseidon.update(&inputs);
+        assert!(poseidon.take_trace().is_empty());
+    }
+
+    #[test]
+    fn poseidon_trace_replays_from_enabled_state() {
+        use halo2curves::bn256::Fq;
+
+        let spec = Spec::<Fr, T, RATE>::new(R_F, R_P);
+        let mut poseidon = Poseidon::<Fr, T, RATE>::new_with_context(spec, "trace");
+        poseidon.set_trace(true);
+        let start = poseidon.state.clone();
+        let msg = gen_random_vec(RATE);
+        poseidon.hash_to_field::<Fq>(&msg, 1);
+        let trace = poseidon.take_trace();
+        assert_eq!(
+            trace[0],
+            AbsorbStep {
+                permutation: 0,
+                slot: 0,
+                value: Fr::from_u128(State::<Fr, 3>::HASH_TO_FIELD_TAG),
+            }
+        );
+
+        let mut state = start;
+        let mut permutation = 0;
+        for step in trace.iter() {
+            while permutation < step.permutation {
+                poseidon.spec.permute(&mut state);
+                permutation += 1;
+            }
+            state.0[step.slot] += step.value;
+        }
+        while permutation < poseidon.permutations {
+            poseidon.spec.permute(&mut state);
+            permutation += 1;
+        }
+        assert_eq!(state, poseidon.state);
+    }
+
     macro_rules! test_padding {
         ($T:expr, $RATE:expr) => {
             paste! {
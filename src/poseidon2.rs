@@ -0,0 +1,284 @@
+//! Poseidon2 permutation. See https://eprint.iacr.org/2023/323.pdf
+
+use crate::grain::Grain;
+use crate::spec::{assert_width, OptimizedConstants, State};
+use crate::Permutation;
+use halo2curves::group::ff::{FromUniformBytes, PrimeField};
+
+/// `Poseidon2Spec` holds round constants and linear layers of Poseidon2.
+/// Round constants are kept in [`OptimizedConstants`] but not in the layout
+/// of [`crate::Spec`]: `start` and `end` hold `r_f / 2` rows of `T` sized
+/// constants, one for each full round, and partial rounds have a single
+/// constant
+#[derive(Debug, Clone)]
+pub struct Poseidon2Spec<F: PrimeField, const T: usize, const RATE: usize> {
+    pub(crate) constants: OptimizedConstants<F, T>,
+    pub(crate) internal_diagonal: [F; T],
+}
+
+impl<F: FromUniformBytes<64>, const T: usize, const RATE: usize> Poseidon2Spec<F, T, RATE> {
+    /// Constructs Poseidon2 parameters for `T = 2` and `T = 3` where the
+    /// internal matrix is fixed by the specification
+    pub fn new(r_f: usize, r_p: usize) -> Self {
+        let internal_diagonal: &[u64] = match T {
+            2 => &[1, 2],
+            3 => &[1, 1, 2],
+            _ => panic!("internal matrix must be given for t = {}", T),
+        };
+        let mut diagonal = [F::ZERO; T];
+        for (d, e) in diagonal.iter_mut().zip(internal_diagonal.iter()) {
+            *d = F::from(*e);
+        }
+        Self::new_with_internal_diagonal(r_f, r_p, diagonal)
+    }
+
+    /// Constructs Poseidon2 parameters with internal matrix `J + diag(d)`
+    /// where `J` is the all ones matrix. `T` must be `2`, `3` or a multiple of
+    /// `4`
+    pub fn new_with_internal_diagonal(r_f: usize, r_p: usize, internal_diagonal: [F; T]) -> Self {
+        assert_width::<T, RATE>();
+        assert!(
+            T == 2 || T == 3 || T.is_multiple_of(4),
+            "unsupported width t = {}",
+            T
+        );
+        // Full rounds draw `T` elements and partial rounds draw a single
+        // element from the same Grain stream
+        let mut grain = Grain::<F, T, RATE>::new(r_f, r_p, &[]);
+        let r_f_half = r_f / 2;
+        let start = (0..r_f_half).map(|_| full_round(&mut grain)).collect();
+        let partial = (0..r_p).map(|_| grain.next_field_element()).collect();
+        let end = (0..r_f_half).map(|_| full_round(&mut grain)).collect();
+        let constants = OptimizedConstants {
+            start,
+            partial,
+            end,
+        };
+        Self {
+            constants,
+            internal_diagonal,
+        }
+    }
+}
+
+impl<F: PrimeField, const T: usize, const RATE: usize> Poseidon2Spec<F, T, RATE> {
+    /// Round constants
+    pub fn constants(&self) -> &OptimizedConstants<F, T> {
+        &self.constants
+    }
+
+    /// Diagonal `d` of the internal matrix `J + diag(d)`
+    pub fn internal_diagonal(&self) -> &[F; T] {
+        &self.internal_diagonal
+    }
+
+    /// Applies the external matrix used in full rounds
+    fn apply_external(&self, state: &mut State<F, T>) {
+        let words = &mut state.0;
+        match T {
+            // `circ(2, 1)` and `circ(2, 1, 1)`
+            2 | 3 => {
+                let sum = words.iter().fold(F::ZERO, |acc, e| acc + e);
+                words.iter_mut().for_each(|e| *e += sum);
+            }
+            // `M4` itself
+            4 => apply_m4(words),
+            // `circ(2 * M4, M4, ..., M4)`
+            _ => {
+                for block in words.chunks_exact_mut(4) {
+                    apply_m4(block);
+                }
+                let mut sums = [F::ZERO; 4];
+                for block in words.chunks_exact(4) {
+                    for (sum, e) in sums.iter_mut().zip(block.iter()) {
+                        *sum += e;
+                    }
+                }
+                for block in words.chunks_exact_mut(4) {
+                    for (e, sum) in block.iter_mut().zip(sums.iter()) {
+                        *e += sum;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Applies the internal matrix `J + diag(d)` used in partial rounds
+    fn apply_internal(&self, state: &mut State<F, T>) {
+        let sum = state.0.iter().fold(F::ZERO, |acc, e| acc + e);
+        for (e, d) in state.0.iter_mut().zip(self.internal_diagonal.iter()) {
+            *e = *e * d + sum;
+        }
+    }
+
+    /// Applies the Poseidon2 permutation to the given state
+    pub fn permute(&self, state: &mut State<F, T>) {
+        self.apply_external(state);
+
+        for constants in self.constants.start.iter() {
+            state.add_constants(constants);
+            state.sbox_full();
+            self.apply_external(state);
+        }
+
+        for constant in self.constants.partial.iter() {
+            state.add_constant(constant);
+            state.sbox_part();
+            self.apply_internal(state);
+        }
+
+        for constants in self.constants.end.iter() {
+            state.add_constants(constants);
+            state.sbox_full();
+            self.apply_external(state);
+        }
+    }
+}
+
+/// Draws constants of a full round
+fn full_round<F: FromUniformBytes<64>, const T: usize, const RATE: usize>(
+    grain: &mut Grain<F, T, RATE>,
+) -> [F; T] {
+    let mut constants = [F::ZERO; T];
+    for c in constants.iter_mut() {
+        *c = grain.next_field_element();
+    }
+    constants
+}
+
+impl<F: PrimeField, const T: usize, const RATE: usize> Permutation<F, T>
+    for Poseidon2Spec<F, T, RATE>
+{
+    fn permute(&self, state: &mut State<F, T>) {
+        Poseidon2Spec::permute(self, state)
+    }
+}
+
+/// Applies `M4 = [[5, 7, 1, 3], [4, 6, 1, 1], [1, 3, 5, 7], [1, 1, 4, 6]]`
+fn apply_m4<F: PrimeField>(x: &mut [F]) {
+    let t0 = x[0] + x[1];
+    let t1 = x[2] + x[3];
+    let t2 = x[1].double() + t1;
+    let t3 = x[3].double() + t0;
+    let t4 = t1.double().double() + t3;
+    let t5 = t0.double().double() + t2;
+    let t6 = t3 + t5;
+    let t7 = t2 + t4;
+    x[0] = t6;
+    x[1] = t5;
+    x[2] = t7;
+    x[3] = t4;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Poseidon2Spec;
+    use crate::State;
+    use halo2curves::bn256::Fr;
+    use halo2curves::group::ff::{Field, PrimeField};
+    use rand_core::OsRng;
+
+    fn from_hex(hex: &str) -> Fr {
+        let mut repr = <Fr as PrimeField>::Repr::default();
+        for (i, byte) in repr.as_mut().iter_mut().rev().enumerate() {
+            *byte = u8::from_str_radix(&hex[2 + 2 * i..4 + 2 * i], 16).unwrap();
+        }
+        Fr::from_repr(repr).unwrap()
+    }
+
+    #[test]
+    fn test_against_test_vectors() {
+        // https://github.com/HorizenLabs/poseidon2/blob/main/plain_implementations/src/poseidon2/poseidon2.rs
+        // BN256, t = 3
+        let spec = Poseidon2Spec::<Fr, 3, 2>::new(8, 56);
+        let mut state = State([0u64, 1, 2].map(Fr::from));
+        spec.permute(&mut state);
+        let expected = [
+            "0x0bb61d24daca55eebcb1929a82650f328134334da98ea4f847f760054f4a3033",
+            "0x303b6f7c86d043bfcbcc80214f26a30277a15d3f74ca654992defe7ff8d03570",
+            "0x1ed25194542b12eef8617361c3ba7c52e660b145994427cc86296242cf766ec8",
+        ];
+        assert_eq!(state.words(), expected.map(from_hex));
+    }
+
+    /// Dense external matrix as in the specification: `M4` for `t = 4` and
+    /// `circ(2 * M4, M4, ..., M4)` for larger multiples of four
+    fn external_matrix<const T: usize>() -> [[Fr; T]; T] {
+        const M4: [[u64; 4]; 4] = [[5, 7, 1, 3], [4, 6, 1, 1], [1, 3, 5, 7], [1, 1, 4, 6]];
+        let mut matrix = [[Fr::ZERO; T]; T];
+        for (i, row) in matrix.iter_mut().enumerate() {
+            for (j, e) in row.iter_mut().enumerate() {
+                let factor = if T > 4 && i / 4 == j / 4 { 2 } else { 1 };
+                *e = Fr::from(factor * M4[i % 4][j % 4]);
+            }
+        }
+        matrix
+    }
+
+    fn check_external<const T: usize, const RATE: usize>(diagonal: [Fr; T]) {
+        let spec = Poseidon2Spec::<Fr, T, RATE>::new_with_internal_diagonal(8, 56, diagonal);
+        let words = [(); T].map(|_| Fr::random(OsRng));
+        let mut state = State(words);
+        spec.apply_external(&mut state);
+        let expected = external_matrix::<T>().map(|row| {
+            row.iter()
+                .zip(words.iter())
+                .fold(Fr::ZERO, |acc, (m, w)| acc + *m * w)
+        });
+        assert_eq!(state.words(), expected);
+    }
+
+    #[test]
+    fn external_matrix_matches_specification() {
+        check_external::<4, 3>([1u64, 2, 3, 4].map(Fr::from));
+        check_external::<8, 7>([1u64, 2, 3, 4, 5, 6, 7, 8].map(Fr::from));
+        check_external::<12, 11>([(); 12].map(|_| Fr::ONE));
+    }
+
+    #[test]
+    #[should_panic(expected = "width mismatch: T = 4 must be RATE + 1 = 3")]
+    fn rejects_width_mismatch() {
+        Poseidon2Spec::<Fr, 4, 2>::new_with_internal_diagonal(8, 56, [Fr::ONE; 4]);
+    }
+
+    #[test]
+    fn test_against_regression_values() {
+        // Regression values computed with this implementation after checking
+        // linear layers against the dense matrices above, not taken from the
+        // reference which only instantiates BN256 with t = 3. Internal
+        // diagonals are chosen here
+        let spec = Poseidon2Spec::<Fr, 4, 3>::new_with_internal_diagonal(
+            8,
+            56,
+            [1u64, 2, 3, 4].map(Fr::from),
+        );
+        let mut state = State([0u64, 1, 2, 3].map(Fr::from));
+        spec.permute(&mut state);
+        let expected = [
+            "0x08958f575e7ef6b3ee3880b055f614d04e323b35c51e6512008660f2814a0ebd",
+            "0x039e36a8b7e04393bb11fbd00f4731ab0752ae4a66e0e062d6779548d44d6db1",
+            "0x06952016c9645f05b185755113bccfe5430520cb48a13e9049cca3cb0b427386",
+            "0x0f7a771e46d87322185317beb43a681053ce5e1f49705819aa7f7a6de53ae413",
+        ];
+        assert_eq!(state.words(), expected.map(from_hex));
+
+        let spec = Poseidon2Spec::<Fr, 8, 7>::new_with_internal_diagonal(
+            8,
+            56,
+            [1u64, 2, 3, 4, 5, 6, 7, 8].map(Fr::from),
+        );
+        let mut state = State([0u64, 1, 2, 3, 4, 5, 6, 7].map(Fr::from));
+        spec.permute(&mut state);
+        let expected = [
+            "0x2feb7cdaa24e6d50ffa77719083f5e7b9e78baafd595a9f04c75fe1511a910da",
+            "0x05f57777efb5b159d9489f575484071ccbbd5e07ae90b839ddb0a23a23e0b5bc",
+            "0x1834e5f971203ef03a3b67b246905aec5fa7f20d51e6339f15e119938d159cc5",
+            "0x030b783103c4f234dddb0fa2c42fb51e9bcb736331db97fefdf043f60701df62",
+            "0x213b521c717efe6b8e72afea0f2efa89f2fe2fab12f39b646c7cc1d611dad114",
+            "0x08da68ccc6173999967abb438c2f8f984d96673e3bb16e65e33ed273ccefbab6",
+            "0x23ec8df531fd5fab4dcd4d11abe25def9ed25a2d040fb0cede49ea101e7e500c",
+            "0x20a50cdabdbe57abced258a69824eae33bdd2a7d2bec18b7c585abaf441e3ef0",
+        ];
+        assert_eq!(state.words(), expected.map(from_hex));
+    }
+}
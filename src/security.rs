@@ -0,0 +1,78 @@
+//! Security level of round numbers against known attacks. Inequalities follow
+//! `sat_inequiv_alpha` of the round numbers script in the reference
+//! implementation
+//! https://extgit.iaik.tugraz.at/krypto/hadeshash/-/blob/master/code/calc_round_numbers.py
+
+use crate::Spec;
+use halo2curves::group::ff::PrimeField;
+
+impl<F: PrimeField, const T: usize, const RATE: usize> Spec<F, T, RATE> {
+    /// Highest security level in bits that round numbers of this `Spec`
+    /// satisfy against statistical, interpolation and Gröbner basis attacks
+    /// for a `field_bits` sized field and `x^alpha` sbox. `log2(p)` is
+    /// approximated with `field_bits`
+    pub fn security_bits(&self, field_bits: u32, alpha: u32) -> u32 {
+        let r_p = self.constants.partial.len();
+        (1..=field_bits * T as u32)
+            .rev()
+            .find(|m| is_secure(*m, field_bits, alpha, T, self.r_f(), r_p))
+            .unwrap_or(0)
+    }
+}
+
+/// Checks if round numbers satisfy security level `m` in bits
+fn is_secure(m: u32, field_bits: u32, alpha: u32, t: usize, r_f: usize, r_p: usize) -> bool {
+    let (m, n, alpha, t) = (m as f64, field_bits as f64, alpha as f64, t as f64);
+    let (r_f, r_p) = (r_f as f64, r_p as f64);
+    let log_alpha = |x: f64| x.ln() / alpha.ln();
+
+    // Statistical
+    let r_f_1 = if m <= (n - (alpha - 1.0) / 2.0).floor() * (t + 1.0) {
+        6.0
+    } else {
+        10.0
+    };
+    // Interpolation
+    let r_f_2 = 1.0 + (log_alpha(2.0) * m.min(n)).ceil() + log_alpha(t).ceil() - r_p;
+    // Gröbner basis
+    let r_f_3 = log_alpha(2.0) * m.min(n) - r_p;
+    let r_f_4 = t - 1.0 + log_alpha(2.0) * (m / (t + 1.0)).min(n / 2.0) - r_p;
+    let r_f_5 = (t - 2.0 + m / (2.0 * alpha.log2()) - r_p) / (t - 1.0);
+    let r_f_max = [r_f_1, r_f_2, r_f_3, r_f_4, r_f_5]
+        .iter()
+        .fold(f64::MIN, |acc, r| acc.max(r.ceil()));
+
+    // Gröbner basis attack of https://eprint.iacr.org/2023/537.pdf
+    let r_temp = (t / 3.0).floor();
+    let over = (r_f - 1.0) * t + r_p + r_temp + r_temp * (r_f / 2.0) + r_p + alpha;
+    let under = r_temp * (r_f / 2.0) + r_p + alpha;
+    let cost_gb4 = (2.0 * log2_binomial(over, under)).ceil();
+
+    r_f >= r_f_max && cost_gb4 >= m
+}
+
+/// `log2` of the binomial coefficient `n choose k`
+fn log2_binomial(n: f64, k: f64) -> f64 {
+    (1..=k as u64)
+        .map(|i| ((n - k + i as f64) / i as f64).log2())
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Spec;
+    use halo2curves::bn256::Fr;
+
+    #[test]
+    fn security_bits() {
+        let spec = Spec::<Fr, 3, 2>::new(8, 57);
+        assert!(spec.security_bits(254, 5) >= 128);
+
+        let spec = Spec::<Fr, 5, 4>::new(8, 60);
+        assert!(spec.security_bits(254, 5) >= 128);
+
+        // Too few partial rounds can't resist interpolation attacks
+        let spec = Spec::<Fr, 3, 2>::new(8, 20);
+        assert!(spec.security_bits(254, 5) < 128);
+    }
+}
@@ -1,7 +1,42 @@
-use crate::{grain::Grain, matrix::Matrix};
+use crate::matrix::{determinant, Matrix};
+use crate::{grain::Grain, SpecError};
 use halo2curves::group::ff::{FromUniformBytes, PrimeField};
+use std::fmt;
 use std::ops::Index;
 
+/// Panics unless the width is a single capacity element followed by `RATE`
+/// elements. Checked in release builds too since a mismatched width silently
+/// yields wrong digests
+pub(crate) fn assert_width<const T: usize, const RATE: usize>() {
+    assert!(
+        T > 1 && T == RATE + 1,
+        "width mismatch: T = {} must be RATE + 1 = {}",
+        T,
+        RATE + 1
+    );
+}
+
+/// Writes a field element as canonical big-endian hex
+fn fmt_hex<F: PrimeField>(f: &mut fmt::Formatter<'_>, e: &F) -> fmt::Result {
+    write!(f, "0x")?;
+    for byte in e.to_repr().as_ref().iter().rev() {
+        write!(f, "{:02x}", byte)?;
+    }
+    Ok(())
+}
+
+/// Writes field elements as a single bracketed row
+fn fmt_row<F: PrimeField>(f: &mut fmt::Formatter<'_>, row: &[F]) -> fmt::Result {
+    write!(f, "[")?;
+    for (i, e) in row.iter().enumerate() {
+        if i != 0 {
+            write!(f, ", ")?;
+        }
+        fmt_hex(f, e)?;
+    }
+    write!(f, "]")
+}
+
 /// `State` is structure `T` sized field elements that are subjected to
 /// permutation
 #[derive(Clone, Debug, PartialEq)]
@@ -10,13 +45,57 @@ pub struct State<F: PrimeField, const T: usize>(pub(crate) [F; T]);
 impl<F: PrimeField, const T: usize> Default for State<F, T> {
     /// The capacity value is 2**64 + (o − 1) where o the output length.
     fn default() -> Self {
-        let mut state = [F::ZERO; T];
-        state[0] = F::from_u128(1 << 64);
-        State(state)
+        State::with_capacity(F::from_u128(Self::VARIABLE_LENGTH_TAG))
     }
 }
 
 impl<F: PrimeField, const T: usize> State<F, T> {
+    /// Capacity value of variable length hashing with single element output
+    pub const VARIABLE_LENGTH_TAG: u128 = 1 << 64;
+
+    /// Added to the capacity element of a forked hasher in
+    /// [`crate::Poseidon::derive`] to separate derived keys from digests
+    pub const DERIVE_TAG: u128 = 1 << 65;
+
+    /// Added to the capacity element of row hashers in
+    /// [`crate::Poseidon::hash_matrix`]
+    pub const MATRIX_ROW_TAG: u128 = 1 << 66;
+
+    /// Added to the capacity element of the hasher that absorbs row digests
+    /// in [`crate::Poseidon::hash_matrix`]
+    pub const MATRIX_TAG: u128 = 1 << 67;
+
+    /// Added to the capacity element in [`crate::Poseidon::hash_commutative`]
+    pub const COMMUTATIVE_TAG: u128 = 1 << 68;
+
+    /// Added to the capacity element in [`crate::Poseidon::hash_sparse`]
+    pub const SPARSE_TAG: u128 = 1 << 69;
+
+    /// Added to the capacity element in [`crate::Poseidon::hash_records`]
+    pub const RECORDS_TAG: u128 = 1 << 70;
+
+    /// Capacity value in [`crate::Poseidon::hash_public_inputs`] when there
+    /// are no inputs
+    pub const PUBLIC_INPUTS_EMPTY_TAG: u128 = 1 << 71;
+
+    /// Added to the capacity element in [`crate::Poseidon::hash_to_field`]
+    pub const HASH_TO_FIELD_TAG: u128 = 1 << 72;
+
+    /// Capacity value of constant length hashing of `len` elements that is
+    /// `2^64 + (len - 1)`. Panics if `len` is zero
+    pub fn constant_length_tag(len: usize) -> F {
+        assert!(len > 0, "constant length input can't be empty");
+        F::from_u128(Self::VARIABLE_LENGTH_TAG + (len as u128 - 1))
+    }
+
+    /// Constructs a state with the given capacity element and zero rate
+    /// elements
+    pub(crate) fn with_capacity(capacity: F) -> Self {
+        let mut state = [F::ZERO; T];
+        state[0] = capacity;
+        State(state)
+    }
+
     /// Applies sbox for all elements of the state.
     /// Only supports `alpha = 5` sbox case.
     pub(crate) fn sbox_full(&mut self) {
@@ -49,14 +128,64 @@ impl<F: PrimeField, const T: usize> State<F, T> {
         self.0[0].add_assign(constant)
     }
 
+    /// Constructs a state from raw words. It is the inverse of `words`
+    ///
+    /// ```
+    /// use halo2curves::bn256::Fr;
+    /// use halo2curves::group::ff::PrimeField;
+    /// use poseidon::{Spec, State};
+    ///
+    /// let words = [0u64, 1, 2].map(Fr::from);
+    /// let mut state = State::from_words(words);
+    /// assert_eq!(state.words(), words);
+    ///
+    /// // poseidonperm_x5_254_3 of the reference test vectors
+    /// Spec::<Fr, 3, 2>::new(8, 57).permute(&mut state);
+    /// assert_eq!(
+    ///     state.words()[0],
+    ///     Fr::from_str_vartime(
+    ///         "7853200120776062878684798364095072458815029376092732009249414926327459813530"
+    ///     )
+    ///     .unwrap()
+    /// );
+    /// ```
+    pub fn from_words(words: [F; T]) -> Self {
+        State(words)
+    }
+
     /// Copies elements of the state
     pub fn words(&self) -> [F; T] {
         self.0
     }
 
-    /// Second element of the state is the result
+    /// Rate part of the state that follows the single capacity element.
+    /// Outputs are read from here
+    pub fn rate_words(&self) -> &[F] {
+        &self.0[1..]
+    }
+
+    /// First element of the rate part is the result
     pub(crate) fn result(&self) -> F {
-        self.0[1]
+        self.rate_words()[0]
+    }
+}
+
+impl<F: PrimeField, const T: usize> fmt::Display for State<F, T> {
+    /// Renders words as canonical big-endian hex
+    ///
+    /// ```
+    /// use halo2curves::bn256::Fr;
+    /// use poseidon::State;
+    ///
+    /// let state = State::<Fr, 2>::default();
+    /// assert_eq!(
+    ///     state.to_string(),
+    ///     "[0x0000000000000000000000000000000000000000000000010000000000000000, \
+    ///     0x0000000000000000000000000000000000000000000000000000000000000000]"
+    /// );
+    /// ```
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt_row(f, &self.0)
     }
 }
 
@@ -64,17 +193,76 @@ impl<F: PrimeField, const T: usize> State<F, T> {
 /// permutation step. Constants are planned to be hardcoded once transcript
 /// design matures. Number of partial rounds can be deriven from number of
 /// constants.
+///
+/// Every step of the derivation needs field arithmetic which is not available
+/// in const contexts: `Grain` samples constants with `from_repr`, MDS
+/// inversion and sparse factorisation need field inversion. So a `Spec`
+/// can't be built at compile time but it can be precomputed. A build script
+/// may run [`Spec::new`] once, emit the byte representations of its parts as
+/// a `const` table and reassemble them with [`Spec::from_parts`] which
+/// involves no derivation work:
+///
+/// ```ignore
+/// // build.rs
+/// let spec = Spec::<Fr, 3, 2>::new(8, 57);
+/// let bytes = |e: &Fr| format!("{:?}", e.to_repr().as_ref());
+/// let start = spec.constants().start().iter()
+///     .map(|row| format!("[{}]", row.iter().map(bytes).collect::<Vec<_>>().join(",")))
+///     .collect::<Vec<_>>();
+/// writeln!(out, "const START: [[[u8; 32]; 3]; {}] = [{}];", start.len(), start.join(","))?;
+/// // ... same for `partial`, `end`, `mds`, `pre_sparse_mds` and sparse matrices
+///
+/// // lib.rs
+/// include!(concat!(env!("OUT_DIR"), "/poseidon_constants.rs"));
+/// let fe = |repr: [u8; 32]| Fr::from_repr(repr).unwrap();
+/// let constants = OptimizedConstants::new(
+///     START.iter().map(|row| row.map(fe)).collect(),
+///     PARTIAL.iter().copied().map(fe).collect(),
+///     END.iter().map(|row| row.map(fe)).collect(),
+/// );
+/// let matrices = MDSMatrices::new(mds, pre_sparse_mds, sparse_matrices);
+/// let spec = Spec::from_parts(4, 4, matrices, constants);
+/// ```
 #[derive(Debug, Clone)]
 pub struct Spec<F: PrimeField, const T: usize, const RATE: usize> {
-    pub(crate) r_f: usize,
+    pub(crate) r_f_first: usize,
+    pub(crate) r_f_last: usize,
     pub(crate) mds_matrices: MDSMatrices<F, T, RATE>,
     pub(crate) constants: OptimizedConstants<F, T>,
 }
 
 impl<F: PrimeField, const T: usize, const RATE: usize> Spec<F, T, RATE> {
+    /// Assembles a `Spec` from already derived matrices and optimized
+    /// constants with `r_f_first` full rounds before and `r_f_last` full
+    /// rounds after the partial rounds. No consistency check is performed,
+    /// see [`Spec::validate`]
+    pub fn from_parts(
+        r_f_first: usize,
+        r_f_last: usize,
+        mds_matrices: MDSMatrices<F, T, RATE>,
+        constants: OptimizedConstants<F, T>,
+    ) -> Self {
+        Self {
+            r_f_first,
+            r_f_last,
+            mds_matrices,
+            constants,
+        }
+    }
+
     /// Number of full rounds
     pub fn r_f(&self) -> usize {
-        self.r_f
+        self.r_f_first + self.r_f_last
+    }
+
+    /// Number of full rounds before the partial rounds
+    pub fn r_f_first(&self) -> usize {
+        self.r_f_first
+    }
+
+    /// Number of full rounds after the partial rounds
+    pub fn r_f_last(&self) -> usize {
+        self.r_f_last
     }
     /// Set of MDS Matrices used in permutation line
     pub fn mds_matrices(&self) -> &MDSMatrices<F, T, RATE> {
@@ -84,6 +272,194 @@ impl<F: PrimeField, const T: usize, const RATE: usize> Spec<F, T, RATE> {
     pub fn constants(&self) -> &OptimizedConstants<F, T> {
         &self.constants
     }
+
+    /// Recovers unoptimized round constants for the reference round layout
+    /// where every round adds `T` constants and applies the dense MDS. It is
+    /// the inverse of [`Spec::calculate_optimized_constants`]. Optimization
+    /// folds partial round constants together so they are not unique: the
+    /// recovered partial round constants are zero but in the first element
+    /// and may differ from the ones generated by `Grain` while yielding the
+    /// same permutation. Panics if there is no full round before the partial
+    /// rounds
+    pub fn to_reference_constants(&self) -> Vec<[F; T]> {
+        let mds = &self.mds_matrices.mds;
+        let start = &self.constants.start;
+        assert!(
+            start.len() > 1,
+            "there must be a full round before the partial rounds"
+        );
+        let r_f_first = start.len() - 1;
+
+        // Constants of the first half are the ones multiplied by inverse MDS
+        let mut constants = vec![start[0]];
+        constants.extend(
+            start
+                .iter()
+                .skip(1)
+                .take(r_f_first - 1)
+                .map(|constants| mds.mul_constants(constants)),
+        );
+
+        // Unroll the accumulator of partial rounds starting from the
+        // constants that are added right before the transition matrix
+        let mut acc = mds.mul_constants(&start[r_f_first]);
+        for optimized in self.constants.partial.iter() {
+            let mut constants_partial = [F::ZERO; T];
+            constants_partial[0] = acc[0];
+            constants.push(constants_partial);
+
+            acc[0] = *optimized;
+            acc = mds.mul_constants(&acc);
+        }
+        constants.push(acc);
+
+        // Constants of the second half are the ones multiplied by inverse MDS
+        constants.extend(
+            self.constants
+                .end
+                .iter()
+                .map(|constants| mds.mul_constants(constants)),
+        );
+        constants
+    }
+
+    /// Number of field multiplications in a single `permute`. An `alpha = 5`
+    /// sbox costs 3 multiplications, dense MDS costs `T * T` and sparse MDS
+    /// costs `T + RATE`
+    pub fn multiplication_count(&self) -> usize {
+        const SBOX: usize = 3;
+        let r_p = self.constants.partial.len();
+        self.r_f() * (T * SBOX + T * T) + r_p * (SBOX + T + RATE)
+    }
+
+    /// Checks internal consistency of a spec that is assembled from external
+    /// parts, e.g. with [`Spec::from_parts`]. There must be at least one full
+    /// round on each side, number of optimized constants must fit the full
+    /// rounds, there must be a sparse matrix for each
+    /// partial round, the MDS matrix must be invertible and sparse matrices
+    /// must factorize it as [`MDSMatrices::verify_factorization`] checks
+    pub fn validate(&self) -> Result<(), SpecError> {
+        if self.r_f_first == 0 || self.r_f_last == 0 {
+            return Err(SpecError::FullRounds(self.r_f()));
+        }
+        for (constants, expected) in [
+            (&self.constants.start, self.r_f_first + 1),
+            (&self.constants.end, self.r_f_last - 1),
+        ] {
+            if constants.len() != expected {
+                return Err(SpecError::ConstantLength {
+                    expected,
+                    found: constants.len(),
+                });
+            }
+        }
+
+        let r_p = self.constants.partial.len();
+        let matrices = &self.mds_matrices;
+        if matrices.sparse_matrices.len() != r_p {
+            return Err(SpecError::SparseMatrixCount {
+                expected: r_p,
+                found: matrices.sparse_matrices.len(),
+            });
+        }
+        self.constants.sanity_check()?;
+        if bool::from(determinant(matrices.mds.as_vec()).is_zero()) {
+            return Err(SpecError::SingularMds);
+        }
+        if !matrices.verify_factorization(r_p) {
+            return Err(SpecError::Factorization);
+        }
+        Ok(())
+    }
+
+    /// Returns the number of rounds of each phase as `permute` applies them.
+    /// Optimized constants don't follow rounds one to one: `start` has an
+    /// extra entry added before the first round while `end` has one entry
+    /// less since the last round adds no constants
+    pub fn round_structure(&self) -> RoundStructure {
+        RoundStructure {
+            full_first: self.r_f_first,
+            partial: self.constants.partial.len(),
+            full_last: self.r_f_last,
+        }
+    }
+
+    /// Summarizes parameters so that a configuration can be logged and
+    /// compared across services
+    pub fn summary(&self) -> SpecSummary {
+        SpecSummary {
+            r_f: self.r_f(),
+            r_f_first: self.r_f_first,
+            r_f_last: self.r_f_last,
+            r_p: self.constants.partial.len(),
+            t: T,
+            rate: RATE,
+            alpha: 5,
+            mds_fingerprint: self.mds_matrices.mds.fingerprint(),
+        }
+    }
+}
+
+/// `ConstantStrategy` selects how round constants and the MDS matrix are
+/// derived
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ConstantStrategy {
+    /// Both round constants and the MDS matrix are sampled with Grain as the
+    /// reference implementation does
+    #[default]
+    Grain,
+    /// Round constants are sampled with Grain while the MDS matrix is the
+    /// Cauchy matrix `1 / (i + T + j)` as neptune and lurk derive it
+    Neptune,
+}
+
+/// `SpecSummary` identifies a Poseidon configuration
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SpecSummary {
+    /// Number of full rounds
+    pub r_f: usize,
+    /// Number of full rounds before the partial rounds
+    pub r_f_first: usize,
+    /// Number of full rounds after the partial rounds
+    pub r_f_last: usize,
+    /// Number of partial rounds
+    pub r_p: usize,
+    /// Width of the state
+    pub t: usize,
+    /// Number of elements absorbed per permutation
+    pub rate: usize,
+    /// Exponent of the sbox
+    pub alpha: u64,
+    /// See [`MDSMatrix::fingerprint`]
+    pub mds_fingerprint: u64,
+}
+
+/// `RoundStructure` is the number of rounds of each phase of the permutation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RoundStructure {
+    /// Full rounds before the partial rounds
+    pub full_first: usize,
+    /// Partial rounds
+    pub partial: usize,
+    /// Full rounds after the partial rounds
+    pub full_last: usize,
+}
+
+impl<F: PrimeField, const T: usize, const RATE: usize> fmt::Display for Spec<F, T, RATE> {
+    /// Renders round parameters followed by the MDS matrix
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "t = {}, rate = {}, r_f = {} ({} + {}), r_p = {}",
+            T,
+            RATE,
+            self.r_f(),
+            self.r_f_first,
+            self.r_f_last,
+            self.constants.partial.len()
+        )?;
+        write!(f, "{}", self.mds_matrices.mds)
+    }
 }
 
 /// `OptimizedConstants` has round constants that are added each round. While
@@ -97,6 +473,38 @@ pub struct OptimizedConstants<F: PrimeField, const T: usize> {
 }
 
 impl<F: PrimeField, const T: usize> OptimizedConstants<F, T> {
+    /// Constructs optimized constants from its parts
+    pub fn new(start: Vec<[F; T]>, partial: Vec<F>, end: Vec<[F; T]>) -> Self {
+        Self {
+            start,
+            partial,
+            end,
+        }
+    }
+
+    /// Flags constants that indicate a failed generation or corrupted
+    /// loading: an all zero round or a round that repeats the previous one.
+    /// Rounds are indexed in order of `start`, `partial` and `end`
+    pub fn sanity_check(&self) -> Result<(), SpecError> {
+        let rounds = self
+            .start
+            .iter()
+            .map(|constants| constants.as_slice())
+            .chain(self.partial.iter().map(std::slice::from_ref))
+            .chain(self.end.iter().map(|constants| constants.as_slice()));
+        let mut previous: Option<&[F]> = None;
+        for (round, constants) in rounds.enumerate() {
+            if constants.iter().all(|c| bool::from(c.is_zero())) {
+                return Err(SpecError::ZeroConstants(round));
+            }
+            if previous == Some(constants) {
+                return Err(SpecError::RepeatedConstants(round));
+            }
+            previous = Some(constants);
+        }
+        Ok(())
+    }
+
     /// Returns rounds constants for first part of full rounds
     pub fn start(&self) -> &Vec<[F; T]> {
         &self.start
@@ -124,6 +532,19 @@ pub struct MDSMatrices<F: PrimeField, const T: usize, const RATE: usize> {
 }
 
 impl<F: PrimeField, const T: usize, const RATE: usize> MDSMatrices<F, T, RATE> {
+    /// Constructs set of MDS matrices from its parts
+    pub fn new(
+        mds: MDSMatrix<F, T, RATE>,
+        pre_sparse_mds: MDSMatrix<F, T, RATE>,
+        sparse_matrices: Vec<SparseMDSMatrix<F, T, RATE>>,
+    ) -> Self {
+        Self {
+            mds,
+            pre_sparse_mds,
+            sparse_matrices,
+        }
+    }
+
     /// Returns original MDS matrix
     pub fn mds(&self) -> &MDSMatrix<F, T, RATE> {
         &self.mds
@@ -138,6 +559,66 @@ impl<F: PrimeField, const T: usize, const RATE: usize> MDSMatrices<F, T, RATE> {
     pub fn sparse_matrices(&self) -> &Vec<SparseMDSMatrix<F, T, RATE>> {
         &self.sparse_matrices
     }
+
+    /// Checks that the transition matrix followed by sparse matrices of `r_p`
+    /// partial rounds amounts to applying the MDS matrix `r_p + 1` times as
+    /// the unoptimized permutation does
+    pub fn verify_factorization(&self, r_p: usize) -> bool {
+        if self.sparse_matrices.len() != r_p {
+            return false;
+        }
+        let product = self
+            .sparse_matrices
+            .iter()
+            .fold(self.pre_sparse_mds.0.clone(), |acc, sparse| {
+                sparse.to_matrix().mul(&acc)
+            });
+        let expected = (0..r_p).fold(self.mds.0.clone(), |acc, _| self.mds.0.mul(&acc));
+        product == expected
+    }
+
+    /// Stacks rows and column hats of sparse matrices in the order they are
+    /// applied in partial rounds of `permute`
+    pub fn sparse_layout(&self) -> SparseLayout<F> {
+        let (rows, col_hats) = self
+            .sparse_matrices
+            .iter()
+            .map(|sparse| (sparse.row.to_vec(), sparse.col_hat.to_vec()))
+            .unzip();
+        SparseLayout { rows, col_hats }
+    }
+}
+
+/// `SparseLayout` holds sparse matrices of partial rounds as two matrices. Row
+/// `i` of both belongs to the `i`th partial round so that column `j` can be
+/// assigned to a fixed column of a circuit region as is
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SparseLayout<F: PrimeField> {
+    rows: Vec<Vec<F>>,
+    col_hats: Vec<Vec<F>>,
+}
+
+impl<F: PrimeField> SparseLayout<F> {
+    /// Returns first rows of sparse matrices. Each row has `T` elements
+    pub fn rows(&self) -> &[Vec<F>] {
+        &self.rows
+    }
+
+    /// Returns first columns of sparse matrices without the first element.
+    /// Each column has `RATE` elements
+    pub fn col_hats(&self) -> &[Vec<F>] {
+        &self.col_hats
+    }
+
+    /// Number of partial rounds
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    /// Returns true if there are no partial rounds
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
 }
 
 /// `MDSMatrix` is applied to `State` to achive linear layer of Poseidon
@@ -145,10 +626,68 @@ impl<F: PrimeField, const T: usize, const RATE: usize> MDSMatrices<F, T, RATE> {
 pub struct MDSMatrix<F: PrimeField, const T: usize, const RATE: usize>(Matrix<F, T>);
 
 impl<F: PrimeField, const T: usize, const RATE: usize> MDSMatrix<F, T, RATE> {
+    /// Constructs the matrix from its rows
+    pub fn from_rows(rows: [[F; T]; T]) -> Self {
+        Self(Matrix(rows))
+    }
+
     /// Get elements of mds matrix
     pub fn as_vec(&self) -> Vec<Vec<F>> {
         self.0 .0.iter().map(|row| row.to_vec()).collect()
     }
+
+    /// FNV-1a hash of canonical representations of the elements in row-major
+    /// order. It identifies a matrix in logs and is not collision resistant
+    pub fn fingerprint(&self) -> u64 {
+        let rows = &self.0 .0;
+        rows.iter()
+            .flatten()
+            .flat_map(|e| e.to_repr().as_ref().to_vec())
+            .fold(0xcbf29ce484222325, |hash, byte| {
+                (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+            })
+    }
+
+    /// Checks that every square submatrix is non-singular. Number of
+    /// submatrices grows exponentially with `T` so it is meant for validating
+    /// user supplied matrices of small widths
+    pub fn is_mds(&self) -> bool {
+        let rows = &self.0 .0;
+        let subsets = |k: u32| (1u32..1 << T).filter(move |mask| mask.count_ones() == k);
+        let indices = |mask: u32| (0..T).filter(move |i| mask & (1 << i) != 0);
+        (1..=T as u32).all(|k| {
+            subsets(k).all(|row_mask| {
+                subsets(k).all(|col_mask| {
+                    let sub = indices(row_mask)
+                        .map(|i| indices(col_mask).map(|j| rows[i][j]).collect())
+                        .collect();
+                    !bool::from(determinant(sub).is_zero())
+                })
+            })
+        })
+    }
+}
+
+impl<F: PrimeField, const T: usize, const RATE: usize> From<[[F; T]; T]> for MDSMatrix<F, T, RATE> {
+    /// Constructs a matrix from its rows. No MDS check is performed, see
+    /// [`MDSMatrix::is_mds`]
+    ///
+    /// ```
+    /// use halo2curves::bn256::Fr;
+    /// use halo2curves::group::ff::Field;
+    /// use poseidon::MDSMatrix;
+    ///
+    /// // Cauchy matrix `1 / (x_i + y_j)` with `x = [0, 1]` and `y = [1, 2]`
+    /// let m = |n: u64| Fr::from(n).invert().unwrap();
+    /// let mds = MDSMatrix::<Fr, 2, 1>::from([[m(1), m(2)], [m(2), m(3)]]);
+    /// assert!(mds.is_mds());
+    ///
+    /// let singular = MDSMatrix::<Fr, 2, 1>::from([[Fr::one(); 2]; 2]);
+    /// assert!(!singular.is_mds());
+    /// ```
+    fn from(rows: [[F; T]; T]) -> Self {
+        Self::from_rows(rows)
+    }
 }
 
 impl<F: PrimeField, const T: usize, const RATE: usize> Index<usize> for MDSMatrix<F, T, RATE> {
@@ -159,10 +698,65 @@ impl<F: PrimeField, const T: usize, const RATE: usize> Index<usize> for MDSMatri
     }
 }
 
+impl<F: PrimeField, const T: usize, const RATE: usize> TryFrom<&[F]> for MDSMatrix<F, T, RATE> {
+    type Error = SpecError;
+
+    /// Reshapes row-major `T * T` elements into a matrix
+    fn try_from(elements: &[F]) -> Result<Self, Self::Error> {
+        if elements.len() != T * T {
+            return Err(SpecError::MatrixLength {
+                expected: T * T,
+                found: elements.len(),
+            });
+        }
+        let mut rows = [[F::ZERO; T]; T];
+        for (row, elements) in rows.iter_mut().zip(elements.chunks(T)) {
+            row.copy_from_slice(elements);
+        }
+        Ok(Self::from_rows(rows))
+    }
+}
+
+impl<F: PrimeField, const T: usize, const RATE: usize> fmt::Display for MDSMatrix<F, T, RATE> {
+    /// Renders one row per line. Hex words are fixed width so columns are
+    /// aligned
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, row) in self.0 .0.iter().enumerate() {
+            if i != 0 {
+                writeln!(f)?;
+            }
+            fmt_row(f, row)?;
+        }
+        Ok(())
+    }
+}
+
 impl<F: PrimeField, const T: usize, const RATE: usize> MDSMatrix<F, T, RATE> {
     /// Applies `MDSMatrix` to the state
     pub(crate) fn apply(&self, state: &mut State<F, T>) {
-        state.0 = self.0.mul_vector(&state.0);
+        self.apply_to(&mut state.0);
+    }
+
+    /// Applies `MDSMatrix` to a bare `T` sized array
+    ///
+    /// ```
+    /// use halo2curves::bn256::Fr;
+    /// use poseidon::Spec;
+    ///
+    /// let spec = Spec::<Fr, 3, 2>::new(8, 57);
+    /// let mds = spec.mds_matrices().mds();
+    ///
+    /// let mut words = [Fr::from(1), Fr::from(2), Fr::from(3)];
+    /// let expected = mds.rows().map(|row| {
+    ///     row.iter()
+    ///         .zip(words.iter())
+    ///         .fold(Fr::zero(), |acc, (m, w)| acc + *m * w)
+    /// });
+    /// mds.apply_to(&mut words);
+    /// assert_eq!(words, expected);
+    /// ```
+    pub fn apply_to(&self, words: &mut [F; T]) {
+        *words = self.0.mul_vector(words);
     }
 
     /// Given two `T` sized vector constructs the `t * t` Cauchy matrix
@@ -180,7 +774,7 @@ impl<F: PrimeField, const T: usize, const RATE: usize> MDSMatrix<F, T, RATE> {
 
     /// Inverts the MDS matrix
     fn invert(&self) -> Self {
-        Self(self.0.invert())
+        Self(self.0.invert().expect("MDS matrix is invertible"))
     }
 
     /// Used in calculation of optimized round constants. Calculates `v' = M *
@@ -202,7 +796,7 @@ impl<F: PrimeField, const T: usize, const RATE: usize> MDSMatrix<F, T, RATE> {
     /// Factorises an MDS matrix `M` into `M'` and `M''` where `M = M' *  M''`.
     /// Resulted `M''` matrices are the sparse ones while `M'` will contribute
     /// to the accumulator of the process
-    fn factorise(&self) -> (Self, SparseMDSMatrix<F, T, RATE>) {
+    fn factorise(&self) -> Result<(Self, SparseMDSMatrix<F, T, RATE>), SpecError> {
         // Given `(t-1 * t-1)` MDS matrix called `hat` constructs the matrix in
         // form `[[1 | 0], [0 | m]]`
         let prime = |hat: Matrix<F, RATE>| -> MDSMatrix<F, T, RATE> {
@@ -228,9 +822,9 @@ impl<F: PrimeField, const T: usize, const RATE: usize> MDSMatrix<F, T, RATE> {
 
         let w = self.0.w();
         let m_hat = self.0.sub::<RATE>();
-        let m_hat_inverse = m_hat.invert();
+        let m_hat_inverse = m_hat.invert().ok_or(SpecError::Factorization)?;
         let w_hat = m_hat_inverse.mul_vector(&w);
-        (prime(m_hat), prime_prime(w_hat).transpose().into())
+        Ok((prime(m_hat), prime_prime(w_hat).transpose().into()))
     }
 
     /// Returns rows of the MDS matrix
@@ -248,6 +842,12 @@ pub struct SparseMDSMatrix<F: PrimeField, const T: usize, const RATE: usize> {
 }
 
 impl<F: PrimeField, const T: usize, const RATE: usize> SparseMDSMatrix<F, T, RATE> {
+    /// Constructs the sparse matrix from its first row and first column
+    /// without the first element
+    pub fn new(row: [F; T], col_hat: [F; RATE]) -> Self {
+        Self { row, col_hat }
+    }
+
     /// Returns the first row
     pub fn row(&self) -> &[F; T] {
         &self.row
@@ -260,33 +860,69 @@ impl<F: PrimeField, const T: usize, const RATE: usize> SparseMDSMatrix<F, T, RAT
 
     /// Applies the sparse MDS matrix to the state
     pub(crate) fn apply(&self, state: &mut State<F, T>) {
-        let words = state.words();
-        state.0[0] = self
+        self.apply_to(&mut state.0);
+    }
+
+    /// Expands the sparse form into a dense matrix
+    pub(crate) fn to_matrix(&self) -> Matrix<F, T> {
+        let mut m = Matrix::identity();
+        m.0[0] = self.row;
+        for (i, e) in self.col_hat.iter().enumerate() {
+            m.0[i + 1][0] = *e;
+        }
+        m
+    }
+
+    /// Applies the sparse MDS matrix to a bare `T` sized array
+    ///
+    /// ```
+    /// use halo2curves::bn256::Fr;
+    /// use poseidon::Spec;
+    ///
+    /// let spec = Spec::<Fr, 3, 2>::new(8, 57);
+    /// let sparse = &spec.mds_matrices().sparse_matrices()[0];
+    ///
+    /// // Dense form is `[row], [col_hat | identity]`
+    /// let mut words = [Fr::from(1), Fr::from(2), Fr::from(3)];
+    /// let (row, col_hat) = (sparse.row(), sparse.col_hat());
+    /// let expected = [
+    ///     row[0] * words[0] + row[1] * words[1] + row[2] * words[2],
+    ///     col_hat[0] * words[0] + words[1],
+    ///     col_hat[1] * words[0] + words[2],
+    /// ];
+    /// sparse.apply_to(&mut words);
+    /// assert_eq!(words, expected);
+    /// ```
+    pub fn apply_to(&self, words: &mut [F; T]) {
+        let input = *words;
+        words[0] = self
             .row
             .iter()
-            .zip(words.iter())
+            .zip(input.iter())
             .fold(F::ZERO, |acc, (e, cell)| acc + (*e * *cell));
 
-        for ((new_word, col_el), word) in (state.0)
+        for ((new_word, col_el), word) in words
             .iter_mut()
             .skip(1)
             .zip(self.col_hat.iter())
-            .zip(words.iter().skip(1))
+            .zip(input.iter().skip(1))
         {
-            *new_word = *col_el * words[0] + word;
+            *new_word = *col_el * input[0] + word;
         }
     }
 }
 
-impl<F: PrimeField, const T: usize, const RATE: usize> From<MDSMatrix<F, T, RATE>>
-    for SparseMDSMatrix<F, T, RATE>
-{
-    /// Assert the form and represent an MDS matrix as a sparse MDS matrix
-    fn from(mds: MDSMatrix<F, T, RATE>) -> Self {
+impl<F: PrimeField, const T: usize, const RATE: usize> SparseMDSMatrix<F, T, RATE> {
+    /// Represents an MDS matrix as a sparse MDS matrix. Fails with the
+    /// position of the first entry that breaks the `[row], [hat | identity]`
+    /// form
+    pub fn try_from_mds(mds: MDSMatrix<F, T, RATE>) -> Result<Self, SpecError> {
         let mds = mds.0;
         for (i, row) in mds.0.iter().enumerate().skip(1) {
-            for (j, _) in row.iter().enumerate().skip(1) {
-                assert_eq!(row[j], if i != j { F::ZERO } else { F::ONE });
+            for (j, el) in row.iter().enumerate().skip(1) {
+                if *el != if i != j { F::ZERO } else { F::ONE } {
+                    return Err(SpecError::NotSparse { row: i, col: j });
+                }
             }
         }
 
@@ -298,7 +934,40 @@ impl<F: PrimeField, const T: usize, const RATE: usize> From<MDSMatrix<F, T, RATE
             *col_el = row[0]
         }
 
-        SparseMDSMatrix { row, col_hat }
+        Ok(SparseMDSMatrix { row, col_hat })
+    }
+}
+
+impl<F: PrimeField, const T: usize, const RATE: usize> From<MDSMatrix<F, T, RATE>>
+    for SparseMDSMatrix<F, T, RATE>
+{
+    /// Assert the form and represent an MDS matrix as a sparse MDS matrix
+    fn from(mds: MDSMatrix<F, T, RATE>) -> Self {
+        match Self::try_from_mds(mds) {
+            Ok(sparse) => sparse,
+            Err(e) => panic!("{}", e),
+        }
+    }
+}
+
+#[cfg(any(test, feature = "perturb"))]
+impl<F: PrimeField, const T: usize, const RATE: usize> Spec<F, T, RATE> {
+    /// Adds `delta` to a single optimized round constant. Rounds are indexed
+    /// in the order constants are used in `permute`: `start` rows, then
+    /// `partial` constants and then `end` rows. Partial rounds have a single
+    /// constant at index `0`. Only meant for differential testing of the
+    /// permutation
+    pub fn perturb_constant(&mut self, round: usize, index: usize, delta: F) {
+        let (n_start, n_partial) = (self.constants.start.len(), self.constants.partial.len());
+        let constant = if round < n_start {
+            &mut self.constants.start[round][index]
+        } else if round < n_start + n_partial {
+            assert_eq!(index, 0, "partial rounds have a single constant");
+            &mut self.constants.partial[round - n_start]
+        } else {
+            &mut self.constants.end[round - n_start - n_partial][index]
+        };
+        *constant += delta;
     }
 }
 
@@ -307,33 +976,138 @@ impl<F: FromUniformBytes<64>, const T: usize, const RATE: usize> Spec<F, T, RATE
     /// calculating unoptimized round constants with reference `Grain` then
     /// calculates optimized constants and sparse matrices
     pub fn new(r_f: usize, r_p: usize) -> Self {
-        let (unoptimized_constants, mds) = Grain::generate(r_f, r_p);
-        let constants = Self::calculate_optimized_constants(r_f, r_p, unoptimized_constants, &mds);
-        let (sparse_matrices, pre_sparse_mds) = Self::calculate_sparse_matrices(r_p, &mds);
+        Self::new_with_domain(r_f, r_p, &[])
+    }
 
-        Self {
-            r_f,
+    /// Constructs a new Poseidon instance deriving constants and the MDS
+    /// matrix with the given strategy
+    pub fn new_with_strategy(r_f: usize, r_p: usize, strategy: ConstantStrategy) -> Self {
+        match strategy {
+            ConstantStrategy::Grain => Self::new(r_f, r_p),
+            ConstantStrategy::Neptune => {
+                assert_width::<T, RATE>();
+                let (constants, mds) = Grain::generate_neptune(r_f, r_p);
+                let constants = constants.iter().map(|row| row.to_vec()).collect::<Vec<_>>();
+                Self::with_constants(r_f, r_p, &constants, mds)
+                    .expect("generated constants have the expected shape")
+            }
+        }
+    }
+
+    /// Constructs a new Poseidon instance from an independent parameter
+    /// family. `domain` bytes additionally seed `Grain` so constants don't
+    /// collide with standard ones. Empty domain yields standard parameters
+    pub fn new_with_domain(r_f: usize, r_p: usize, domain: &[u8]) -> Self {
+        assert_width::<T, RATE>();
+        let (unoptimized_constants, mds) = Grain::generate_with_domain(r_f, r_p, domain);
+        Self::assemble(r_f / 2, r_f / 2, r_p, unoptimized_constants, mds)
+            .expect("generated MDS matrix factorizes")
+    }
+
+    /// Constructs a new Poseidon instance where full rounds are split unevenly
+    /// around the partial rounds. `Grain` is seeded with the total number of
+    /// full rounds which must be even. Equal halves yield [`Spec::new`]
+    pub fn new_with_full_rounds(r_f_first: usize, r_f_last: usize, r_p: usize) -> Self {
+        assert_width::<T, RATE>();
+        assert!(
+            r_f_first > 0 && r_f_last > 0,
+            "there must be a full round on each side"
+        );
+        assert!(
+            (r_f_first + r_f_last).is_multiple_of(2),
+            "total number of full rounds must be even"
+        );
+        let (unoptimized_constants, mds) = Grain::generate(r_f_first + r_f_last, r_p);
+        Self::assemble(r_f_first, r_f_last, r_p, unoptimized_constants, mds)
+            .expect("generated MDS matrix factorizes")
+    }
+
+    /// Constructs a new Poseidon instance from externally given unoptimized
+    /// round constants and MDS matrix. Optimized constants and sparse matrices
+    /// are derived as in [`Spec::new`]. There must be `r_f + r_p` rounds of
+    /// `T` constants, `r_f` must be even and nonzero and the MDS matrix must
+    /// be invertible and factorize into sparse matrices
+    pub fn with_constants(
+        r_f: usize,
+        r_p: usize,
+        constants: &[Vec<F>],
+        mds: MDSMatrix<F, T, RATE>,
+    ) -> Result<Self, SpecError> {
+        assert_width::<T, RATE>();
+        if r_f == 0 || !r_f.is_multiple_of(2) {
+            return Err(SpecError::FullRounds(r_f));
+        }
+        if bool::from(determinant(mds.as_vec()).is_zero()) {
+            return Err(SpecError::SingularMds);
+        }
+        if constants.len() != r_f + r_p {
+            return Err(SpecError::ConstantLength {
+                expected: r_f + r_p,
+                found: constants.len(),
+            });
+        }
+        let constants = constants
+            .iter()
+            .map(|row| {
+                <[F; T]>::try_from(row.as_slice()).map_err(|_| SpecError::ConstantLength {
+                    expected: T,
+                    found: row.len(),
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Self::assemble(r_f / 2, r_f / 2, r_p, constants, mds)
+    }
+
+    /// Derives optimized constants and sparse matrices from unoptimized round
+    /// constants and an invertible MDS matrix. Fails if the matrix doesn't
+    /// factorize into sparse matrices
+    fn assemble(
+        r_f_first: usize,
+        r_f_last: usize,
+        r_p: usize,
+        constants: Vec<[F; T]>,
+        mds: MDSMatrix<F, T, RATE>,
+    ) -> Result<Self, SpecError> {
+        let (sparse_matrices, pre_sparse_mds) = Self::try_calculate_sparse_matrices(r_p, &mds)?;
+        let constants = Self::optimize_constants(r_f_first, r_f_last, r_p, constants, &mds);
+        Ok(Self {
+            r_f_first,
+            r_f_last,
             constants,
             mds_matrices: MDSMatrices {
                 mds,
                 sparse_matrices,
                 pre_sparse_mds,
             },
-        }
+        })
     }
 
-    fn calculate_optimized_constants(
+    /// Derives optimized constants from the unoptimized round constants
+    /// generated by `Grain`. See [`SpecRef`]
+    pub fn calculate_optimized_constants(
         r_f: usize,
         r_p: usize,
         constants: Vec<[F; T]>,
         mds: &MDSMatrix<F, T, RATE>,
+    ) -> OptimizedConstants<F, T> {
+        Self::optimize_constants(r_f / 2, r_f / 2, r_p, constants, mds)
+    }
+
+    /// Same as `calculate_optimized_constants` with full rounds split into
+    /// `r_f_first` and `r_f_last`
+    fn optimize_constants(
+        r_f_first: usize,
+        r_f_last: usize,
+        r_p: usize,
+        constants: Vec<[F; T]>,
+        mds: &MDSMatrix<F, T, RATE>,
     ) -> OptimizedConstants<F, T> {
         let inverse_mds = mds.invert();
-        let (number_of_rounds, r_f_half) = (r_f + r_p, r_f / 2);
+        let number_of_rounds = r_f_first + r_p + r_f_last;
         assert_eq!(constants.len(), number_of_rounds);
 
         // Calculate optimized constants for first half of the full rounds
-        let mut constants_start: Vec<[F; T]> = vec![[F::ZERO; T]; r_f_half];
+        let mut constants_start: Vec<[F; T]> = vec![[F::ZERO; T]; r_f_first];
         constants_start[0] = constants[0];
         for (optimized, constants) in constants_start
             .iter_mut()
@@ -344,12 +1118,12 @@ impl<F: FromUniformBytes<64>, const T: usize, const RATE: usize> Spec<F, T, RATE
         }
 
         // Calculate constants for partial rounds
-        let mut acc = constants[r_f_half + r_p];
+        let mut acc = constants[r_f_first + r_p];
         let mut constants_partial = vec![F::ZERO; r_p];
         for (optimized, constants) in constants_partial
             .iter_mut()
             .rev()
-            .zip(constants.iter().skip(r_f_half).rev().skip(r_f_half))
+            .zip(constants.iter().skip(r_f_first).rev().skip(r_f_last))
         {
             let mut tmp = inverse_mds.mul_constants(&acc);
             *optimized = tmp[0];
@@ -363,10 +1137,10 @@ impl<F: FromUniformBytes<64>, const T: usize, const RATE: usize> Spec<F, T, RATE
         constants_start.push(inverse_mds.mul_constants(&acc));
 
         // Calculate optimized constants for ending half of the full rounds
-        let mut constants_end: Vec<[F; T]> = vec![[F::ZERO; T]; r_f_half - 1];
+        let mut constants_end: Vec<[F; T]> = vec![[F::ZERO; T]; r_f_last - 1];
         for (optimized, constants) in constants_end
             .iter_mut()
-            .zip(constants.iter().skip(r_f_half + r_p + 1))
+            .zip(constants.iter().skip(r_f_first + r_p + 1))
         {
             *optimized = inverse_mds.mul_constants(constants);
         }
@@ -378,22 +1152,35 @@ impl<F: FromUniformBytes<64>, const T: usize, const RATE: usize> Spec<F, T, RATE
         }
     }
 
-    fn calculate_sparse_matrices(
+    /// Factorises the MDS matrix into sparse matrices for partial rounds.
+    /// Returns sparse matrices and the transition matrix. Panics if a
+    /// submatrix met in the process is singular, which doesn't happen for
+    /// MDS matrices
+    pub fn calculate_sparse_matrices(
         r_p: usize,
         mds: &MDSMatrix<F, T, RATE>,
     ) -> (Vec<SparseMDSMatrix<F, T, RATE>>, MDSMatrix<F, T, RATE>) {
+        Self::try_calculate_sparse_matrices(r_p, mds).expect("MDS matrix factorizes")
+    }
+
+    /// Same as `calculate_sparse_matrices` but fails with
+    /// [`SpecError::Factorization`] instead of panicking
+    fn try_calculate_sparse_matrices(
+        r_p: usize,
+        mds: &MDSMatrix<F, T, RATE>,
+    ) -> Result<(Vec<SparseMDSMatrix<F, T, RATE>>, MDSMatrix<F, T, RATE>), SpecError> {
         let mds = mds.transpose();
         let mut acc = mds.clone();
         let mut sparse_matrices = (0..r_p)
             .map(|_| {
-                let (m_prime, m_prime_prime) = acc.factorise();
+                let (m_prime, m_prime_prime) = acc.factorise()?;
                 acc = mds.mul(&m_prime);
-                m_prime_prime
+                Ok(m_prime_prime)
             })
-            .collect::<Vec<SparseMDSMatrix<F, T, RATE>>>();
+            .collect::<Result<Vec<SparseMDSMatrix<F, T, RATE>>, SpecError>>()?;
 
         sparse_matrices.reverse();
-        (sparse_matrices, acc.transpose())
+        Ok((sparse_matrices, acc.transpose()))
     }
 }
 
@@ -437,3 +1224,496 @@ impl<F: FromUniformBytes<64>, const T: usize, const RATE: usize> SpecRef<F, T, R
         &self.constants
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        MDSMatrices, MDSMatrix, OptimizedConstants, SparseMDSMatrix, Spec, SpecSummary, State,
+    };
+    use crate::{matrix::Matrix, SpecError};
+    use halo2curves::bn256::Fr;
+    use halo2curves::group::ff::{Field, PrimeField};
+    use rand_core::OsRng;
+
+    #[test]
+    fn spec_from_parts() {
+        let spec = Spec::<Fr, 3, 2>::new(8, 57);
+
+        // Pass all parts through their byte representations as a build script
+        // would do
+        let fe = |e: &Fr| Fr::from_repr(e.to_repr()).unwrap();
+        let row = |row: &[Fr; 3]| row.map(|e| fe(&e));
+        let matrix = |m: &MDSMatrix<Fr, 3, 2>| MDSMatrix::from_rows(m.rows().map(|r| row(&r)));
+
+        let constants = spec.constants();
+        let constants = OptimizedConstants::new(
+            constants.start().iter().map(row).collect(),
+            constants.partial().iter().map(fe).collect(),
+            constants.end().iter().map(row).collect(),
+        );
+        let matrices = spec.mds_matrices();
+        let sparse_matrices = matrices
+            .sparse_matrices()
+            .iter()
+            .map(|m| SparseMDSMatrix::new(row(m.row()), m.col_hat().map(|e| fe(&e))))
+            .collect();
+        let matrices = MDSMatrices::new(
+            matrix(matrices.mds()),
+            matrix(matrices.pre_sparse_mds()),
+            sparse_matrices,
+        );
+        let rebuilt = Spec::from_parts(spec.r_f_first(), spec.r_f_last(), matrices, constants);
+
+        let mut state_0 = State([0; 3].map(|_| Fr::random(OsRng)));
+        let mut state_1 = state_0.clone();
+        spec.permute(&mut state_0);
+        rebuilt.permute(&mut state_1);
+        assert_eq!(state_0, state_1);
+    }
+
+    #[test]
+    fn rate_words() {
+        let state = State::<Fr, 5>(
+            (0..5u64)
+                .map(Fr::from)
+                .collect::<Vec<_>>()
+                .try_into()
+                .unwrap(),
+        );
+        assert_eq!(state.rate_words(), &[1u64, 2, 3, 4].map(Fr::from));
+        assert_eq!(state.result(), Fr::from(1));
+    }
+
+    #[test]
+    fn capacity_tags() {
+        let bytes = |tag: Fr| tag.to_repr().as_ref().to_vec();
+        let mut expected = vec![0u8; 32];
+
+        expected[8] = 1;
+        let tag = Fr::from_u128(State::<Fr, 3>::VARIABLE_LENGTH_TAG);
+        assert_eq!(bytes(tag), expected);
+        assert_eq!(State::<Fr, 3>::default().0[0], tag);
+        assert_eq!(bytes(State::<Fr, 3>::constant_length_tag(1)), expected);
+
+        expected[0] = 2;
+        assert_eq!(bytes(State::<Fr, 3>::constant_length_tag(3)), expected);
+
+        // Mode tags set a single bit above the variable length tag
+        for (tag, byte, value) in [
+            (State::<Fr, 3>::DERIVE_TAG, 8, 0x02),
+            (State::<Fr, 3>::MATRIX_ROW_TAG, 8, 0x04),
+            (State::<Fr, 3>::MATRIX_TAG, 8, 0x08),
+            (State::<Fr, 3>::COMMUTATIVE_TAG, 8, 0x10),
+            (State::<Fr, 3>::SPARSE_TAG, 8, 0x20),
+            (State::<Fr, 3>::RECORDS_TAG, 8, 0x40),
+            (State::<Fr, 3>::PUBLIC_INPUTS_EMPTY_TAG, 8, 0x80),
+            (State::<Fr, 3>::HASH_TO_FIELD_TAG, 9, 0x01),
+        ] {
+            let mut expected = vec![0u8; 32];
+            expected[byte] = value;
+            assert_eq!(bytes(Fr::from_u128(tag)), expected);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "there must be a full round before the partial rounds")]
+    fn reference_constants_without_first_full_rounds() {
+        let mut spec = Spec::<Fr, 3, 2>::new(8, 57);
+        spec.constants.start.clear();
+        spec.to_reference_constants();
+    }
+
+    #[test]
+    #[should_panic(expected = "constant length input can't be empty")]
+    fn empty_constant_length_tag() {
+        State::<Fr, 3>::constant_length_tag(0);
+    }
+
+    #[test]
+    fn spec_with_constants() {
+        use crate::grain::Grain;
+
+        let (constants, mds) = Grain::<Fr, 3, 2>::generate(8, 57);
+        let mut constants = constants.iter().map(|row| row.to_vec()).collect::<Vec<_>>();
+        let spec = Spec::<Fr, 3, 2>::with_constants(8, 57, &constants, mds.clone()).unwrap();
+        let standard = Spec::<Fr, 3, 2>::new(8, 57);
+        let mut state_0 = State([(); 3].map(|_| Fr::random(OsRng)));
+        let mut state_1 = state_0.clone();
+        spec.permute(&mut state_0);
+        standard.permute(&mut state_1);
+        assert_eq!(state_0, state_1);
+
+        constants[10].pop();
+        assert_eq!(
+            Spec::<Fr, 3, 2>::with_constants(8, 57, &constants, mds.clone()).unwrap_err(),
+            SpecError::ConstantLength {
+                expected: 3,
+                found: 2
+            }
+        );
+        assert_eq!(
+            Spec::<Fr, 3, 2>::with_constants(8, 57, &constants[1..], mds.clone()).unwrap_err(),
+            SpecError::ConstantLength {
+                expected: 65,
+                found: 64
+            }
+        );
+
+        // Full rounds must split evenly with one on each side
+        let constants = &constants[..64];
+        for r_f in [0, 7] {
+            assert_eq!(
+                Spec::<Fr, 3, 2>::with_constants(r_f, 64 - r_f, constants, mds.clone())
+                    .unwrap_err(),
+                SpecError::FullRounds(r_f)
+            );
+        }
+
+        let singular = MDSMatrix::from([[Fr::one(); 3]; 3]);
+        assert_eq!(
+            Spec::<Fr, 3, 2>::with_constants(8, 56, constants, singular).unwrap_err(),
+            SpecError::SingularMds
+        );
+
+        // Invertible but its lower right block is singular
+        let (constants, _) = Grain::<Fr, 3, 2>::generate(8, 57);
+        let constants = constants.iter().map(|row| row.to_vec()).collect::<Vec<_>>();
+        let (zero, one) = (Fr::zero(), Fr::one());
+        let swap = MDSMatrix::from([[zero, one, zero], [one, zero, zero], [zero, zero, one]]);
+        assert_eq!(
+            Spec::<Fr, 3, 2>::with_constants(8, 57, &constants, swap).unwrap_err(),
+            SpecError::Factorization
+        );
+    }
+
+    #[test]
+    fn spec_with_domain() {
+        let standard = Spec::<Fr, 3, 2>::new(8, 57);
+        assert_eq!(
+            Spec::<Fr, 3, 2>::new_with_domain(8, 57, &[]).to_reference_constants(),
+            standard.to_reference_constants()
+        );
+
+        let spec = Spec::<Fr, 3, 2>::new_with_domain(8, 57, b"custom family");
+        assert_ne!(
+            spec.to_reference_constants(),
+            standard.to_reference_constants()
+        );
+        assert_ne!(
+            spec.mds_matrices().mds().rows(),
+            standard.mds_matrices().mds().rows()
+        );
+
+        // MDS: every square submatrix is non-singular
+        let m = spec.mds_matrices().mds().rows();
+        let det2 = |r: [usize; 2], c: [usize; 2]| {
+            m[r[0]][c[0]] * m[r[1]][c[1]] - m[r[0]][c[1]] * m[r[1]][c[0]]
+        };
+        let pairs = [[0, 1], [0, 2], [1, 2]];
+        for row in m.iter() {
+            assert!(row.iter().all(|e| !bool::from(e.is_zero())));
+        }
+        for r in pairs {
+            for c in pairs {
+                assert!(!bool::from(det2(r, c).is_zero()));
+            }
+        }
+        let det3 = m[0][0] * det2([1, 2], [1, 2]) - m[0][1] * det2([1, 2], [0, 2])
+            + m[0][2] * det2([1, 2], [0, 1]);
+        assert!(!bool::from(det3.is_zero()));
+
+        // `x^5` is a permutation as `gcd(5, p - 1) = 1`. Since `256 = 1 mod 5`
+        // residue of `p - 1` is the sum of its bytes
+        let p_minus_one = -Fr::ONE;
+        let residue = p_minus_one
+            .to_repr()
+            .as_ref()
+            .iter()
+            .fold(0u64, |acc, b| acc + *b as u64)
+            % 5;
+        assert_ne!(residue, 0);
+
+        // Constants still define a valid permutation
+        let mut state_0 = State([0; 3].map(|_| Fr::random(OsRng)));
+        let mut state_1 = state_0.clone();
+        spec.permute(&mut state_0);
+        spec.permute_naive(&mut state_1);
+        assert_eq!(state_0, state_1);
+    }
+
+    #[test]
+    fn mds_from_flat_slice() {
+        let spec = Spec::<Fr, 3, 2>::new(8, 57);
+        let mds = spec.mds_matrices().mds();
+        let flat = mds.rows().concat();
+
+        let reshaped = MDSMatrix::<Fr, 3, 2>::try_from(&flat[..]).unwrap();
+        assert_eq!(reshaped.rows(), mds.rows());
+
+        let err = MDSMatrix::<Fr, 3, 2>::try_from(&flat[1..]).unwrap_err();
+        assert_eq!(
+            err,
+            SpecError::MatrixLength {
+                expected: 9,
+                found: 8
+            }
+        );
+    }
+
+    #[test]
+    fn generated_matrices_are_mds() {
+        assert!(Spec::<Fr, 3, 2>::new(8, 57).mds_matrices().mds().is_mds());
+        assert!(Spec::<Fr, 5, 4>::new(8, 60).mds_matrices().mds().is_mds());
+
+        let mut rows = Spec::<Fr, 3, 2>::new(8, 57).mds_matrices().mds().rows();
+        rows[1] = rows[0];
+        assert!(!MDSMatrix::<Fr, 3, 2>::from(rows).is_mds());
+    }
+
+    #[test]
+    fn neptune_strategy() {
+        use crate::grain::Grain;
+        use crate::ConstantStrategy;
+
+        let spec = Spec::<Fr, 3, 2>::new_with_strategy(8, 55, ConstantStrategy::Neptune);
+        // neptune MDS matrix for arity 2 is `1 / (i + 3 + j)`
+        let rows = spec.mds_matrices().mds().rows();
+        for (i, row) in rows.iter().enumerate() {
+            for (j, e) in row.iter().enumerate() {
+                assert_eq!(*e * Fr::from((i + 3 + j) as u64), Fr::one());
+            }
+        }
+        assert!(spec.mds_matrices().verify_factorization(55));
+
+        // Round constants follow the same Grain stream as standard parameters
+        let (constants, _) = Grain::<Fr, 3, 2>::generate(8, 55);
+        let mds = spec.mds_matrices().mds().clone();
+        let rebuilt = Spec::<Fr, 3, 2>::with_constants(
+            8,
+            55,
+            &constants.iter().map(|row| row.to_vec()).collect::<Vec<_>>(),
+            mds,
+        )
+        .unwrap();
+        assert_eq!(
+            spec.to_reference_constants(),
+            rebuilt.to_reference_constants()
+        );
+        assert_ne!(
+            spec.to_reference_constants(),
+            Spec::<Fr, 3, 2>::new_with_strategy(8, 55, ConstantStrategy::Grain)
+                .to_reference_constants()
+        );
+
+        // First round constants and the arity 2 hash of `[1, 2]` where the
+        // capacity holds neptune's `2^arity - 1` tag. These are regression
+        // values computed with this strategy and don't prove compatibility.
+        // TODO: replace with output of neptune over BN256 at arity 2
+        let expected = [
+            "16732400965758335251779392048426587810957736297383109721274516509564352746234",
+            "13658450279998882202467741350699437599150295127008655396322514276516522143822",
+            "4770856174779368421444016364886990723103507322593233096050105202383197768378",
+        ]
+        .map(|e| Fr::from_str_vartime(e).unwrap());
+        assert_eq!(spec.to_reference_constants()[0], expected);
+
+        let mut state = State([Fr::from(3), Fr::from(1), Fr::from(2)]);
+        spec.permute(&mut state);
+        assert_eq!(
+            state.0[1],
+            Fr::from_str_vartime(
+                "6106060244281009708053057403848867661494082861286407800351231038313588594079"
+            )
+            .unwrap()
+        );
+    }
+
+    #[test]
+    fn spec_validate() {
+        let spec = Spec::<Fr, 3, 2>::new(8, 57);
+        assert_eq!(spec.validate(), Ok(()));
+
+        let mut broken = spec.clone();
+        broken.r_f_last = 0;
+        assert_eq!(broken.validate(), Err(SpecError::FullRounds(4)));
+
+        let mut broken = spec.clone();
+        broken.constants.start.pop();
+        assert_eq!(
+            broken.validate(),
+            Err(SpecError::ConstantLength {
+                expected: 5,
+                found: 4
+            })
+        );
+
+        let mut broken = spec.clone();
+        broken.constants.end.push([Fr::zero(); 3]);
+        assert_eq!(
+            broken.validate(),
+            Err(SpecError::ConstantLength {
+                expected: 3,
+                found: 4
+            })
+        );
+
+        let mut broken = spec.clone();
+        broken.mds_matrices.sparse_matrices.pop();
+        assert_eq!(
+            broken.validate(),
+            Err(SpecError::SparseMatrixCount {
+                expected: 57,
+                found: 56
+            })
+        );
+
+        let mut broken = spec.clone();
+        let mut rows = broken.mds_matrices.mds.rows();
+        rows[2] = rows[1];
+        broken.mds_matrices.mds = MDSMatrix::from_rows(rows);
+        assert_eq!(broken.validate(), Err(SpecError::SingularMds));
+
+        let mut broken = spec;
+        broken.mds_matrices.sparse_matrices[0].row[0] += Fr::one();
+        assert_eq!(broken.validate(), Err(SpecError::Factorization));
+    }
+
+    #[test]
+    fn constants_sanity_check() {
+        let spec = Spec::<Fr, 3, 2>::new(8, 57);
+        assert_eq!(spec.constants().sanity_check(), Ok(()));
+
+        let mut broken = spec.clone();
+        broken.constants.start[2] = [Fr::zero(); 3];
+        assert_eq!(
+            broken.constants.sanity_check(),
+            Err(SpecError::ZeroConstants(2))
+        );
+        assert_eq!(broken.validate(), Err(SpecError::ZeroConstants(2)));
+
+        let mut broken = spec.clone();
+        broken.constants.partial[1] = broken.constants.partial[0];
+        assert_eq!(
+            broken.constants.sanity_check(),
+            Err(SpecError::RepeatedConstants(6))
+        );
+
+        let mut broken = spec;
+        broken.constants.end[0] = [Fr::one(); 3];
+        broken.constants.end[1] = [Fr::one(); 3];
+        assert_eq!(
+            broken.constants.sanity_check(),
+            Err(SpecError::RepeatedConstants(5 + 57 + 1))
+        );
+    }
+
+    #[test]
+    fn round_structure() {
+        use crate::RoundStructure;
+
+        let spec = Spec::<Fr, 3, 2>::new(8, 57);
+        let rounds = spec.round_structure();
+        assert_eq!(
+            rounds,
+            RoundStructure {
+                full_first: 4,
+                partial: 57,
+                full_last: 4
+            }
+        );
+
+        let constants = spec.constants();
+        assert_eq!(constants.start.len(), rounds.full_first + 1);
+        assert_eq!(constants.partial.len(), rounds.partial);
+        assert_eq!(constants.end.len(), rounds.full_last - 1);
+        assert_eq!(
+            rounds.full_first + rounds.partial + rounds.full_last,
+            spec.to_reference_constants().len()
+        );
+    }
+
+    #[test]
+    fn spec_summary() {
+        let spec = Spec::<Fr, 3, 2>::new(8, 57);
+        let summary = spec.summary();
+        assert_eq!(
+            summary,
+            SpecSummary {
+                r_f: 8,
+                r_f_first: 4,
+                r_f_last: 4,
+                r_p: 57,
+                t: 3,
+                rate: 2,
+                alpha: 5,
+                mds_fingerprint: spec.mds_matrices().mds().fingerprint(),
+            }
+        );
+        let other = Spec::<Fr, 3, 2>::new_with_domain(8, 57, b"other");
+        assert_ne!(summary.mds_fingerprint, other.summary().mds_fingerprint);
+
+        // Uneven split is reported and rendered
+        let uneven = Spec::<Fr, 3, 2>::new_with_full_rounds(3, 5, 57);
+        let uneven_summary = uneven.summary();
+        assert_eq!(
+            (
+                uneven_summary.r_f,
+                uneven_summary.r_f_first,
+                uneven_summary.r_f_last
+            ),
+            (8, 3, 5)
+        );
+        assert_ne!(uneven_summary, summary);
+        let header = |spec: &Spec<Fr, 3, 2>| spec.to_string().lines().next().unwrap().to_string();
+        assert_eq!(header(&spec), "t = 3, rate = 2, r_f = 8 (4 + 4), r_p = 57");
+        assert_eq!(
+            header(&uneven),
+            "t = 3, rate = 2, r_f = 8 (3 + 5), r_p = 57"
+        );
+    }
+
+    #[test]
+    fn verify_factorization() {
+        let spec = Spec::<Fr, 5, 4>::new(8, 60);
+        let mut matrices = spec.mds_matrices().clone();
+        assert!(matrices.verify_factorization(60));
+        assert!(!matrices.verify_factorization(59));
+
+        matrices.sparse_matrices[7].col_hat[2] += Fr::one();
+        assert!(!matrices.verify_factorization(60));
+    }
+
+    #[test]
+    fn apply_to_matches_state_apply() {
+        let spec = Spec::<Fr, 5, 4>::new(8, 60);
+        let words = [(); 5].map(|_| Fr::random(OsRng));
+
+        let mds = spec.mds_matrices().mds();
+        let (mut state, mut bare) = (State(words), words);
+        mds.apply(&mut state);
+        mds.apply_to(&mut bare);
+        assert_eq!(state.0, bare);
+
+        let sparse = &spec.mds_matrices().sparse_matrices()[0];
+        let (mut state, mut bare) = (State(words), words);
+        sparse.apply(&mut state);
+        sparse.apply_to(&mut bare);
+        assert_eq!(state.0, bare);
+    }
+
+    #[test]
+    fn sparse_form_violation_is_reported() {
+        let mut m = Matrix::<Fr, 3>::identity();
+        m.0[0] = [Fr::from(1), Fr::from(2), Fr::from(3)];
+        m.0[1][0] = Fr::from(4);
+        m.0[2][0] = Fr::from(5);
+
+        let sparse = SparseMDSMatrix::<Fr, 3, 2>::try_from_mds(MDSMatrix(m.clone())).unwrap();
+        assert_eq!(sparse.row(), &[Fr::from(1), Fr::from(2), Fr::from(3)]);
+        assert_eq!(sparse.col_hat(), &[Fr::from(4), Fr::from(5)]);
+
+        m.0[2][1] = Fr::from(6);
+        let err = SparseMDSMatrix::<Fr, 3, 2>::try_from_mds(MDSMatrix(m)).unwrap_err();
+        assert_eq!(err, SpecError::NotSparse { row: 2, col: 1 });
+    }
+}
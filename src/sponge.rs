@@ -0,0 +1,161 @@
+//! Duplex sponge over an arbitrary permutation without padding or domain
+//! separation. It is meant as a building block for constructions that need
+//! control over the sponge layout. See [`crate::Poseidon`] for hashing
+
+use crate::permutation::Permutation;
+use crate::spec::State;
+use halo2curves::group::ff::PrimeField;
+
+/// `Sponge` absorbs into and squeezes from the rate part of a `T` sized state.
+/// The first `capacity` elements are never touched directly. Absorb and
+/// squeeze phases may use different rates, both `T - capacity` at most
+#[derive(Debug, Clone)]
+pub struct Sponge<F: PrimeField, const T: usize, P: Permutation<F, T>> {
+    permutation: P,
+    state: State<F, T>,
+    capacity: usize,
+    absorb_rate: usize,
+    squeeze_rate: usize,
+    squeezing: bool,
+    position: usize,
+}
+
+impl<F: PrimeField, const T: usize, P: Permutation<F, T>> Sponge<F, T, P> {
+    /// Constructs a sponge with zero state, capacity of a single element and
+    /// both rates `T - 1`
+    pub fn new(permutation: P) -> Self {
+        Self::new_with_rates(permutation, 1, T - 1, T - 1)
+    }
+
+    /// Constructs a sponge with zero state and the given capacity and rates.
+    /// Panics if a rate is zero or exceeds `T - capacity`
+    pub fn new_with_rates(
+        permutation: P,
+        capacity: usize,
+        absorb_rate: usize,
+        squeeze_rate: usize,
+    ) -> Self {
+        assert!(capacity > 0 && capacity < T, "capacity must be in [1, T)");
+        for rate in [absorb_rate, squeeze_rate] {
+            assert!(
+                rate > 0 && rate <= T - capacity,
+                "rate {} must be in [1, {}]",
+                rate,
+                T - capacity
+            );
+        }
+        Self {
+            permutation,
+            state: State([F::ZERO; T]),
+            capacity,
+            absorb_rate,
+            squeeze_rate,
+            squeezing: false,
+            position: 0,
+        }
+    }
+
+    /// Rate of the absorb phase
+    pub fn absorb_rate(&self) -> usize {
+        self.absorb_rate
+    }
+
+    /// Rate of the squeeze phase
+    pub fn squeeze_rate(&self) -> usize {
+        self.squeeze_rate
+    }
+
+    /// Adds elements to the rate part. State is permuted when the absorb
+    /// rate is exhausted and more elements follow
+    pub fn absorb(&mut self, elements: &[F]) {
+        for element in elements.iter() {
+            if self.squeezing {
+                self.squeezing = false;
+                self.position = 0;
+            }
+            if self.position == self.absorb_rate {
+                self.permutation.permute(&mut self.state);
+                self.position = 0;
+            }
+            self.state.0[self.capacity + self.position] += element;
+            self.position += 1;
+        }
+    }
+
+    /// Returns the next element of the rate part. State is permuted when
+    /// switching from absorbing or when the squeeze rate is exhausted
+    pub fn squeeze(&mut self) -> F {
+        if !self.squeezing || self.position == self.squeeze_rate {
+            self.permutation.permute(&mut self.state);
+            self.squeezing = true;
+            self.position = 0;
+        }
+        let output = self.state.0[self.capacity + self.position];
+        self.position += 1;
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Sponge;
+    use crate::{Spec, State};
+    use halo2curves::bn256::Fr;
+    use halo2curves::group::ff::Field;
+    use rand_core::OsRng;
+
+    #[test]
+    fn sponge_default_rates() {
+        let spec = Spec::<Fr, 3, 2>::new(8, 57);
+        let inputs = [0; 3].map(|_| Fr::random(OsRng));
+
+        let mut sponge = Sponge::new(spec.clone());
+        assert_eq!((sponge.absorb_rate(), sponge.squeeze_rate()), (2, 2));
+        sponge.absorb(&inputs);
+        let outputs = [0; 3].map(|_| sponge.squeeze());
+
+        let mut state = State([Fr::zero(); 3]);
+        state.0[1] += inputs[0];
+        state.0[2] += inputs[1];
+        spec.permute(&mut state);
+        state.0[1] += inputs[2];
+        spec.permute(&mut state);
+        assert_eq!(outputs[..2], state.0[1..]);
+        spec.permute(&mut state);
+        assert_eq!(outputs[2], state.0[1]);
+    }
+
+    #[test]
+    fn sponge_asymmetric_rates() {
+        let spec = Spec::<Fr, 4, 3>::new(8, 56);
+        let inputs = [0; 3].map(|_| Fr::random(OsRng));
+
+        let mut sponge = Sponge::new_with_rates(spec.clone(), 1, 2, 1);
+        sponge.absorb(&inputs);
+        let outputs = [0; 2].map(|_| sponge.squeeze());
+
+        // Absorb fills two slots per permutation while squeeze reads one
+        let mut state = State([Fr::zero(); 4]);
+        state.0[1] += inputs[0];
+        state.0[2] += inputs[1];
+        spec.permute(&mut state);
+        state.0[1] += inputs[2];
+        spec.permute(&mut state);
+        assert_eq!(outputs[0], state.0[1]);
+        spec.permute(&mut state);
+        assert_eq!(outputs[1], state.0[1]);
+
+        // Absorbing after squeezing starts a new absorb phase
+        let input = Fr::random(OsRng);
+        sponge.absorb(&[input]);
+        state.0[1] += input;
+        spec.permute(&mut state);
+        assert_eq!(sponge.squeeze(), state.0[1]);
+    }
+
+    #[test]
+    #[should_panic(expected = "rate 3 must be in [1, 2]")]
+    fn sponge_rejects_wide_rate() {
+        Sponge::new_with_rates(Spec::<Fr, 4, 3>::new(8, 56), 2, 2, 3);
+    }
+}